@@ -2,35 +2,68 @@
 pub const KEY_NULL: i32 = 0;
 pub const KEY_EOT: i32 = 4;
 pub const KEY_BS: i32 = 8;
+pub const KEY_HT: i32 = 9;
 pub const KEY_LF: i32 = 10;
+pub const KEY_DC2: i32 = 18;
+pub const KEY_DC4: i32 = 20;
 pub const KEY_NAK: i32 = 21;
+pub const KEY_SUB: i32 = 26;
 pub const KEY_ETB: i32 = 27;
 pub const KEY_ESC: i32 = 33;
+pub const KEY_PLUS: i32 = 43;
+pub const KEY_MINUS: i32 = 45;
 pub const KEY_FORWARD_SLASH: i32 = 47;
 pub const KEY_COLON: i32 = 58;
+pub const KEY_B_UPPER: i32 = 66;
 pub const KEY_C_UPPER: i32 = 67;
+pub const KEY_F_UPPER: i32 = 70;
+pub const KEY_J_UPPER: i32 = 74;
+pub const KEY_K_UPPER: i32 = 75;
 pub const KEY_N_UPPER: i32 = 78;
 pub const KEY_O_UPPER: i32 = 79;
 pub const KEY_P_UPPER: i32 = 80;
 pub const KEY_R_UPPER: i32 = 82;
+pub const KEY_U_UPPER: i32 = 85;
+pub const KEY_X_UPPER: i32 = 88;
+pub const KEY_A_LOWER: i32 = 97;
 pub const KEY_B_LOWER: i32 = 98;
 pub const KEY_C_LOWER: i32 = 99;
 pub const KEY_D_LOWER: i32 = 100;
+pub const KEY_E_LOWER: i32 = 101;
+pub const KEY_F_LOWER: i32 = 102;
+pub const KEY_G_LOWER: i32 = 103;
 pub const KEY_Q_LOWER: i32 = 113;
 pub const KEY_H_LOWER: i32 = 104;
+pub const KEY_I_LOWER: i32 = 105;
 pub const KEY_J_LOWER: i32 = 106;
 pub const KEY_K_LOWER: i32 = 107;
 pub const KEY_L_LOWER: i32 = 108;
+pub const KEY_M_LOWER: i32 = 109;
 pub const KEY_N_LOWER: i32 = 110;
 pub const KEY_P_LOWER: i32 = 112;
+pub const KEY_R_LOWER: i32 = 114;
+pub const KEY_S_LOWER: i32 = 115;
 pub const KEY_T_LOWER: i32 = 116;
 pub const KEY_U_LOWER: i32 = 117;
 pub const KEY_W_LOWER: i32 = 119;
+pub const KEY_X_LOWER: i32 = 120;
 pub const KEY_Y_LOWER: i32 = 121;
 pub const KEY_DEL: i32 = 127;
 pub const KEY_ZERO: i32 = 48;
 pub const KEY_DOLLAR: i32 = 36;
 
+// Keys outside of ASCII use the ncurses key codes, the crossterm
+// backend translates its key events to the same values.
+pub const KEY_ARROW_DOWN: i32 = 258;
+pub const KEY_ARROW_UP: i32 = 259;
+pub const KEY_ARROW_LEFT: i32 = 260;
+pub const KEY_ARROW_RIGHT: i32 = 261;
+pub const KEY_HOME: i32 = 262;
+pub const KEY_BACKSPACE: i32 = 263;
+pub const KEY_DELETE: i32 = 330;
+pub const KEY_BTAB: i32 = 353;
+pub const KEY_END: i32 = 360;
+
 const ASCII_CHAR_TABLE: [&str; 128] = [
     "NUL", "SOH", "STX", "ETX", "EOT", "ENQ", "ACK", "BEL", "BS", "\t", "LF", "VT", "FF", "CR",
     "SO", "SI", "DLE", "DC1", "DC2", "DC3", "DC4", "NAK", "SYN", "ETB", "CAN", "EM", "SUB", "ESC",
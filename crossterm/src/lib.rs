@@ -1,9 +1,9 @@
-use std::collections::VecDeque;
 use std::io::{Write, Stdout, stdout};
+use std::time::Duration;
 use crossterm::{
     queue,
     execute,
-    event::{KeyEvent, KeyModifiers, KeyCode, Event, read},
+    event::{KeyEvent, KeyModifiers, KeyCode, Event, poll, read},
     style::{Print, Color, Color::Rgb, Stylize, StyledContent},
     cursor,
     terminal::{self, enable_raw_mode, ClearType}
@@ -13,6 +13,8 @@ extern crate term_size;
 
 use gitko_render::{DrawScreen, Line, ScreenSize, Position, Style};
 use gitko_common::num;
+use gitko_common::ascii_table::{KEY_LF, KEY_ETB, KEY_ARROW_DOWN, KEY_ARROW_UP, KEY_ARROW_LEFT, KEY_ARROW_RIGHT,
+                                KEY_HOME, KEY_END, KEY_BACKSPACE, KEY_DELETE, KEY_HT, KEY_BTAB};
 
 pub static mut MAX_WIDTH: i32   = 0;
 pub static mut MAX_HEIGHT: i32  = 0;
@@ -41,6 +43,16 @@ pub fn exit() {
     ).unwrap();
 }
 
+// Hands the terminal over to another program, e.g. an editor.
+pub fn suspend() {
+    exit();
+}
+
+pub fn resume() {
+    enable_raw_mode().unwrap();
+    execute!(stdout(), terminal::Clear(ClearType::All)).unwrap();
+}
+
 pub struct CrosstermWindow {
     lines: Vec<Line>,
     data: Vec<String>,
@@ -49,6 +61,7 @@ pub struct CrosstermWindow {
     screen_start: Position,
     cursor_position: Position,
     cursor_shown: bool,
+    wrap: bool,
     stdout: Stdout
 }
 
@@ -64,6 +77,7 @@ impl CrosstermWindow {
             screen_start: Position::default(),
             cursor_position: Position::default(),
             cursor_shown: true,
+            wrap: true,
             stdout: stdout()
         };
 
@@ -75,6 +89,11 @@ impl CrosstermWindow {
             crossterm_window.screen_start = position;
         }
 
+        // Windows without a size at the bottom of the screen, like
+        // prompts, end with the terminal.
+        crossterm_window.height = crossterm_window.height.min(rows as i32 - position.y).max(1);
+        crossterm_window.width  = crossterm_window.width.min(cols as i32 - position.x).max(1);
+
         crossterm_window
     }
 
@@ -90,37 +109,12 @@ impl CrosstermWindow {
         (x, y)
     }
 
-    fn fit_line(&self, part: &str, max_width: usize) -> Vec<String> {
-        if part.len() < max_width {
-            return vec![part.to_owned()]
-        }
-
-        let mut lines: Vec<String> = vec![];
-
-        let first = &part[..max_width];
-        let mut rest = &part[max_width..];
-
-        lines.push(first.to_owned());
-
-        while rest.len() >= max_width {
-            let first = &rest[..max_width];
-            lines.push(first.to_owned());
-
-            rest = &rest[max_width..];
-        }
-
-        if !rest.is_empty() {
-            lines.push(rest.to_owned());
-        }
-
-        lines
-    }
-
     fn parse_line(&self, line: &Line) -> Vec<Vec<StyledContent<String>>> {
-        let mut lines: VecDeque<Vec<StyledContent<String>>> = VecDeque::new();
-        let mut styled_line: Vec<StyledContent<String>> = vec![];
+        let width = self.width.max(1) as usize;
+        let mut lines: Vec<Vec<StyledContent<String>>> = vec![vec![]];
+        let mut column = 0;
 
-        for (i, part) in line.parts.iter().enumerate() {
+        for part in &line.parts {
             let mut output_str = part.value.clone().stylize();
 
             for style in &part.styles {
@@ -152,47 +146,34 @@ impl CrosstermWindow {
                 }
             }
 
-            let output = part.value.clone();
-            let current_len = output.len();
-            let previous_length: usize = line
-                .parts[..i]
-                .iter()
-                .map(|p| p.value.len())
-                .sum();
+            // Parts over the width of the window continue on the next
+            // row, as many rows as it takes, or are cut off.
+            let style = *output_str.style();
+            let mut rest = part.value.as_str();
+
+            loop {
+                let fits = width - column;
+                let split = rest
+                    .char_indices()
+                    .nth(fits)
+                    .map(|(i, _)| i)
+                    .unwrap_or(rest.len());
+
+                let (first, second) = rest.split_at(split);
+                if !first.is_empty() {
+                    lines.last_mut().unwrap().push(StyledContent::new(style, first.to_owned()));
+                    column += first.chars().count();
+                }
+
+                if second.is_empty() || !self.wrap { break }
 
-            // Checks if the current part is over the screen width.
-            let over_width = previous_length + current_len > self.width as usize;
-
-            // If the current part is over the screen width, split it apart
-            // and render the second part into the next line.
-            if over_width {
-                let idx = current_len - ((previous_length + current_len) - self.width as usize);
-                let style  = output_str.style().clone();
-
-                let first  = &output[..idx];
-                let second = &output[idx..];
-                styled_line.push(StyledContent::new(style, first.to_string()));
-
-                // The current part might be over a line wide, so we need to keep
-                // splitting it until it fits.
-                let split_lines = self.fit_line(second, self.width as usize);
-
-                let content: Vec<StyledContent<String>> = split_lines
-                    .iter()
-                    .map(|l| StyledContent::new(style, l.to_string()))
-                    .collect();
-
-                // Write each split part as a line.
-                content
-                    .iter()
-                    .for_each(|l| lines.push_back(vec![l.clone()]));
-            } else {
-                styled_line.push(output_str);
+                lines.push(vec![]);
+                column = 0;
+                rest = second;
             }
         }
 
-        lines.push_front(styled_line);
-        lines.into()
+        lines
     }
 
     fn create_screen_data(&self) -> Vec<Vec<StyledContent<String>>> {
@@ -205,7 +186,7 @@ impl CrosstermWindow {
 
 impl DrawScreen for CrosstermWindow {
     fn set_data(&mut self, lines: Vec<Line>) {
-        if lines.len() == 0 {
+        if lines.is_empty() {
             self.lines = vec![Line::plain("")]
         } else {
             self.lines = lines;
@@ -221,11 +202,6 @@ impl DrawScreen for CrosstermWindow {
     }
 
     fn resize(&mut self, new_size: ScreenSize) {
-        let columns = new_size.cols.try_into().unwrap();
-        let rows    = new_size.lines.try_into().unwrap();
-
-        terminal::SetSize(columns, rows);
-
         self.height = new_size.lines;
         self.width  = new_size.cols;
     }
@@ -239,6 +215,10 @@ impl DrawScreen for CrosstermWindow {
         }
     }
 
+    fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
     fn get_cursor_line(&self) -> String {
         let index = self.cursor_position.y as usize;
         if index > self.lines.len() {
@@ -254,17 +234,20 @@ impl DrawScreen for CrosstermWindow {
 
         self.data.clear();
 
-        queue!(
-            self.stdout,
-            cursor::MoveTo(start_x, start_y),
-            terminal::Clear(ClearType::FromCursorDown)
-        ).unwrap();
-
+        // Only the rows of the window are drawn over, other windows
+        // can be next to it, e.g. the panes of a layout.
         let screen_data = self.create_screen_data();
-        for (line_number, styled_line) in screen_data.iter().enumerate() {
-            let cursor_line = line_number as i32 == self.cursor_position.y && self.cursor_shown;
+        for row in 0..self.height {
+            queue!(self.stdout, cursor::MoveTo(start_x, start_y + row as u16))
+                .unwrap();
+
+            let cursor_line = row == self.cursor_position.y && self.cursor_shown;
+            let styled_line = screen_data
+                .get(row as usize)
+                .map(|l| l.as_slice())
+                .unwrap_or_default();
 
-            for (part_idx, part) in styled_line.iter().enumerate() {
+            for part in styled_line {
                 if cursor_line {
                     queue!(self.stdout, Print(part.clone().on(HIGHLIGHT_COLOR)))
                         .unwrap();
@@ -272,65 +255,37 @@ impl DrawScreen for CrosstermWindow {
                     queue!(self.stdout, Print(part))
                         .unwrap();
                 }
+            }
 
-                // output_str contains only the text context, so it is
-                // required to highlight the rest of the line as well.
-                let last_part = part_idx == styled_line.len() - 1;
-                if cursor_line && last_part {
-                    let line_length: usize = styled_line
-                        .iter()
-                        .map(|p| p.content().len())
-                        .sum();
-
-                    if line_length >= self.width  as usize {
-                        continue
-                    }
+            // output_str contains only the text context, the rest of the
+            // row is cleared, or highlighted on the cursor line.
+            let line_length: usize = styled_line
+                .iter()
+                .map(|p| p.content().chars().count())
+                .sum();
 
-                    let filler = format!(
-                        "{text:<width$}",
-                        text  = "",
-                        width = self.width as usize - line_length
-                    );
+            if line_length < self.width as usize {
+                let filler = format!(
+                    "{text:<width$}",
+                    text  = "",
+                    width = self.width as usize - line_length
+                );
 
+                if cursor_line {
                     queue!(self.stdout, Print(filler.on(HIGHLIGHT_COLOR)))
                         .unwrap();
+                } else {
+                    queue!(self.stdout, Print(filler))
+                        .unwrap();
                 }
             }
-
-            // TODO: inefficient
-            let string_data: String = styled_line
-                .iter()
-                .map(|p| p.content())
-                .fold(String::new(), |agg, p| agg + p);
-
-            self.data.push(string_data);
-
-            queue!(self.stdout, cursor::MoveToNextLine(1))
-                .unwrap();
         }
 
-        // This is a fix for overflowing the possition in case it is 0.
-        // When it happened, the first row was always overwritten by
-        // the highlighting.
-        let current_cursor_y_pos = if self.cursor_position.y == 0 { 0 }
-                                   else                           { self.cursor_position.y - 1 };
-
-        // We highlight the cursor line while looping through the lines,
-        // but if the cursor is beyond the lines, we still need to do it.
-        if self.cursor_shown && current_cursor_y_pos as usize >= self.lines.len() {
-            let filler = format!(
-                "{text:<width$}",
-                text  = "",
-                width = self.width as usize
-            );
-
-            queue!(
-                self.stdout,
-                cursor::MoveTo(cursor_x, cursor_y),
-                Print(filler.on(HIGHLIGHT_COLOR)),
-                cursor::MoveTo(start_x, start_y),
-            ).unwrap();
-        }
+        // TODO: inefficient
+        self.data = screen_data
+            .iter()
+            .map(|l| l.iter().map(|p| p.content()).fold(String::new(), |agg, p| agg + p))
+            .collect();
 
         queue!(self.stdout, cursor::MoveTo(cursor_x, cursor_y))
             .unwrap();
@@ -341,12 +296,18 @@ impl DrawScreen for CrosstermWindow {
     }
 
     fn clear(&mut self) {
-        let (x, y) = self.cursor_position();
-        execute!(
-            self.stdout,
-            cursor::MoveTo(x, y + 1),
-            terminal::Clear(ClearType::FromCursorDown)
-        ).unwrap();
+        let (start_x, start_y) = self.screen_start();
+        let filler = " ".repeat(self.width as usize);
+
+        for row in 0..self.height {
+            queue!(
+                self.stdout,
+                cursor::MoveTo(start_x, start_y + row as u16),
+                Print(&filler)
+            ).unwrap();
+        }
+
+        self.stdout.flush().unwrap();
     }
 
     // Returns the delta between the attempted cursor
@@ -371,7 +332,7 @@ impl DrawScreen for CrosstermWindow {
                 .unwrap();
         }
 
-        self.cursor_position = Position { x: x.into(), y: y.into() };
+        self.cursor_position = Position { x, y };
 
         (delta, self.cursor_position)
     }
@@ -385,116 +346,104 @@ impl DrawScreen for CrosstermWindow {
 
     fn listen_input(&self) -> i32 {
         loop {
-            match read().unwrap() {
-                Event::Key(KeyEvent { modifiers: KeyModifiers::CONTROL, code }) => {
-                    match code {
-                        KeyCode::Char('d') => return 4,
-                        KeyCode::Char('u') => return 21,
-                        KeyCode::Char(c)   => return c as i32,
-                        _ => { }
-                    }
-                },
-                Event::Key(event) => {
-                    match event.code {
-                        KeyCode::Enter   => return 10,
-                        KeyCode::Esc     => return 27,
-                        KeyCode::Char(c) => return c as i32,
-                        _ => {}
-                    }
-                }
-                _ => {}
+            if let Some(key) = key_code(read().unwrap()) {
+                return key
             }
         }
     }
 
+    fn poll_input(&self, timeout: Duration) -> Option<i32> {
+        // Events which are not keys, like resizes, still end the wait.
+        if !poll(timeout).unwrap_or(false) { return None }
+
+        key_code(read().unwrap())
+    }
+
     fn listen(&mut self) {
         'input_loop:
         loop {
-            match read().unwrap() {
-                Event::Key(event) => {
-                    match event.code {
-                        KeyCode::Esc     => break 'input_loop,
-                        KeyCode::Enter   => break 'input_loop,
-                        KeyCode::Char(c) => {
-                            let mut line = self.lines[0].value();
-                            let index = self.cursor_position.x as usize;
-
-                            line.insert(index, c);
-
-                            self.lines[0] = Line::plain(&line.clone());
-                            self.data[0] = line.clone();
-
-                            self.cursor_position.move_right(1);
-                            let (x, y) = self.cursor_position();
-
-                            execute!(
-                                self.stdout,
-                                terminal::Clear(ClearType::CurrentLine),
-                                cursor::MoveLeft(line.len() as u16 - 1),
-                                Print(line),
-                                cursor::MoveTo(x, y)
-                            ).unwrap();
-                        },
-                        KeyCode::Backspace =>  {
-                            let mut line = self.lines[0].value();
-                            if line.is_empty() { continue }
-
+            if let Event::Key(event) = read().unwrap() {
+                match event.code {
+                    KeyCode::Esc     => break 'input_loop,
+                    KeyCode::Enter   => break 'input_loop,
+                    KeyCode::Char(c) => {
+                        let mut line = self.lines[0].value();
+                        let index = self.cursor_position.x as usize;
+
+                        line.insert(index, c);
+
+                        self.lines[0] = Line::plain(&line.clone());
+                        self.data[0] = line.clone();
+
+                        self.cursor_position.move_right(1);
+                        let (x, y) = self.cursor_position();
+
+                        execute!(
+                            self.stdout,
+                            terminal::Clear(ClearType::CurrentLine),
+                            cursor::MoveLeft(line.len() as u16 - 1),
+                            Print(line),
+                            cursor::MoveTo(x, y)
+                        ).unwrap();
+                    },
+                    KeyCode::Backspace =>  {
+                        let mut line = self.lines[0].value();
+                        if line.is_empty() { continue }
+
+                        self.cursor_position.move_left(1);
+                        let index = self.cursor_position.x as usize;
+
+                        line.remove(index);
+                        self.lines[0] = Line::plain(&line);
+                        self.data[0] = line.clone();
+
+                        let (x, y) = self.cursor_position();
+                        execute!(
+                            self.stdout,
+                            terminal::Clear(ClearType::CurrentLine),
+                            cursor::MoveLeft(line.len() as u16 + 1),
+                            Print(line),
+                            cursor::MoveTo(x, y)
+                        ).unwrap();
+                    },
+                    KeyCode::Delete => {
+                        let mut line = self.lines[0].value();
+                        if line.is_empty() { continue }
+
+                        let index = self.cursor_position.x as usize;
+                        if index >= line.len() { continue }
+
+                        line.remove(index);
+                        self.lines[0] = Line::plain(&line);
+                        self.data[0] = line.clone();
+
+                        let (x, y) = self.cursor_position();
+                        execute!(
+                            self.stdout,
+                            terminal::Clear(ClearType::CurrentLine),
+                            cursor::MoveLeft(line.len() as u16 + 1),
+                            Print(line),
+                            cursor::MoveTo(x, y),
+                        ).unwrap();
+                    },
+                    KeyCode::Left  => {
+                        let next = self.cursor_position.x;
+                        if next > 0 {
                             self.cursor_position.move_left(1);
-                            let index = self.cursor_position.x as usize;
-
-                            line.remove(index);
-                            self.lines[0] = Line::plain(&line);
-                            self.data[0] = line.clone();
-
-                            let (x, y) = self.cursor_position();
-                            execute!(
-                                self.stdout,
-                                terminal::Clear(ClearType::CurrentLine),
-                                cursor::MoveLeft(line.len() as u16 + 1),
-                                Print(line),
-                                cursor::MoveTo(x, y)
-                            ).unwrap();
-                        },
-                        KeyCode::Delete => {
-                            let mut line = self.lines[0].value();
-                            if line.is_empty() { continue }
-
-                            let index = self.cursor_position.x as usize;
-                            if index >= line.len() { continue }
-
-                            line.remove(index);
-                            self.lines[0] = Line::plain(&line);
-                            self.data[0] = line.clone();
-
-                            let (x, y) = self.cursor_position();
-                            execute!(
-                                self.stdout,
-                                terminal::Clear(ClearType::CurrentLine),
-                                cursor::MoveLeft(line.len() as u16 + 1),
-                                Print(line),
-                                cursor::MoveTo(x, y),
-                            ).unwrap();
-                        },
-                        KeyCode::Left  => {
-                            let next = self.cursor_position.x;
-                            if next > 0 {
-                                self.cursor_position.move_left(1);
-                                execute!(self.stdout, cursor::MoveLeft(1)).unwrap();
-                            }
+                            execute!(self.stdout, cursor::MoveLeft(1)).unwrap();
                         }
-                        KeyCode::Right => {
-                            let line = self.get_cursor_line();
-
-                            let next = self.cursor_position.x + 1;
-                            if next <= line.len() as i32 {
-                                self.cursor_position.move_right(1);
-                                execute!(self.stdout, cursor::MoveRight(1)).unwrap();
-                            }
+                    }
+                    KeyCode::Right => {
+                        let line = self.get_cursor_line();
+
+                        let next = self.cursor_position.x + 1;
+                        if next <= line.len() as i32 {
+                            self.cursor_position.move_right(1);
+                            execute!(self.stdout, cursor::MoveRight(1)).unwrap();
                         }
-                        _ => {  }
                     }
+                    _ => {  }
                 }
-                _ => {  }
             }
         }
     }
@@ -505,3 +454,108 @@ impl Drop for CrosstermWindow {
         execute!(self.stdout, cursor::Show).unwrap();
     }
 }
+
+// Translates key events to the codes ncurses would return for them.
+fn key_code(event: Event) -> Option<i32> {
+    match event {
+        Event::Key(KeyEvent { modifiers: KeyModifiers::CONTROL, code }) => {
+            match code {
+                // Same codes a terminal sends, e.g. 4 for ctrl + d.
+                KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+                    Some((c.to_ascii_lowercase() as u8 & 0x1f) as i32)
+                },
+                KeyCode::Char(c) => Some(c as i32),
+                _ => None
+            }
+        },
+        Event::Key(event) => {
+            match event.code {
+                KeyCode::Enter     => Some(KEY_LF),
+                KeyCode::Esc       => Some(KEY_ETB),
+                KeyCode::Char(c)   => Some(c as i32),
+                KeyCode::Down      => Some(KEY_ARROW_DOWN),
+                KeyCode::Up        => Some(KEY_ARROW_UP),
+                KeyCode::Left      => Some(KEY_ARROW_LEFT),
+                KeyCode::Right     => Some(KEY_ARROW_RIGHT),
+                KeyCode::Home      => Some(KEY_HOME),
+                KeyCode::End       => Some(KEY_END),
+                KeyCode::Backspace => Some(KEY_BACKSPACE),
+                KeyCode::Delete    => Some(KEY_DELETE),
+                KeyCode::Tab       => Some(KEY_HT),
+                KeyCode::BackTab   => Some(KEY_BTAB),
+                _ => None
+            }
+        }
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gitko_render::Part;
+
+    fn parse_line(line: &Line, width: i32, wrap: bool) -> Vec<Vec<StyledContent<String>>> {
+        let window = CrosstermWindow {
+            lines: vec![],
+            data: vec![],
+            height: 10,
+            width,
+            screen_start: Position::default(),
+            cursor_position: Position::default(),
+            cursor_shown: true,
+            wrap,
+            stdout: stdout()
+        };
+
+        let parsed = window.parse_line(line);
+
+        // Dropping it would show the cursor in the test output.
+        std::mem::forget(window);
+        parsed
+    }
+
+    fn rows(parsed: Vec<Vec<StyledContent<String>>>) -> Vec<String> {
+        parsed
+            .iter()
+            .map(|row| row.iter().map(|c| c.content().as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn parse_line_wraps_parts_over_the_width() {
+        let line = Line::new(vec![Part::plain("abc"), Part::plain("défgh")]);
+
+        assert_eq!(rows(parse_line(&line, 4, true)), vec!["abcd", "éfgh"]);
+    }
+
+    #[test]
+    fn parse_line_cuts_off_without_wrap() {
+        let line = Line::new(vec![Part::plain("abc"), Part::plain("défgh")]);
+
+        assert_eq!(rows(parse_line(&line, 4, false)), vec!["abcd"]);
+    }
+
+    #[test]
+    fn parse_line_keeps_the_style_of_split_parts() {
+        let line = Line::new(vec![Part::painted("abcdef", (255, 0, 0), (0, 0, 0))]);
+        let parsed = parse_line(&line, 4, true);
+
+        let foreground = Some(Rgb { r: 255, g: 0, b: 0 });
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed.iter().flatten().all(|c| c.style().foreground_color == foreground));
+    }
+    #[test]
+    fn key_code_matches_the_terminal_codes() {
+        let key = |code, modifiers| key_code(Event::Key(KeyEvent::new(code, modifiers)));
+
+        assert_eq!(key(KeyCode::Char('d'), KeyModifiers::CONTROL), Some(4));
+        assert_eq!(key(KeyCode::Char('D'), KeyModifiers::CONTROL), Some(4));
+        assert_eq!(key(KeyCode::Char('j'), KeyModifiers::NONE), Some('j' as i32));
+        assert_eq!(key(KeyCode::Char('J'), KeyModifiers::SHIFT), Some('J' as i32));
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), Some(KEY_LF));
+        assert_eq!(key(KeyCode::Esc, KeyModifiers::NONE), Some(KEY_ETB));
+        assert_eq!(key(KeyCode::BackTab, KeyModifiers::SHIFT), Some(KEY_BTAB));
+        assert_eq!(key(KeyCode::F(1), KeyModifiers::NONE), None);
+    }
+}
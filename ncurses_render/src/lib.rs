@@ -1,4 +1,5 @@
 use std::convert::TryInto;
+use std::time::Duration;
 use gitko_render::{Line, DrawScreen, ScreenSize, Position, Style};
 use gitko_common::{num, ascii_table::*};
 
@@ -29,6 +30,17 @@ pub fn init() {
 
 pub fn exit () { }
 
+// Hands the terminal over to another program, e.g. an editor.
+pub fn suspend() {
+    ncurses::def_prog_mode();
+    ncurses::endwin();
+}
+
+pub fn resume() {
+    ncurses::reset_prog_mode();
+    ncurses::refresh();
+}
+
 pub fn screen_factory(size: ScreenSize, position: Position) -> Box<dyn DrawScreen> {
     Box::new(CursesWindow::new(size, position))
 }
@@ -43,6 +55,7 @@ pub struct CursesWindow {
     position: Position,
     pub cursor_position: Position,
     cursor_hidden: bool,
+    wrap: bool,
     curses_window: ncurses::WINDOW
 }
 
@@ -69,6 +82,7 @@ impl CursesWindow {
             position: Position::default(),
             cursor_position: Position::default(),
             cursor_hidden: false,
+            wrap: true,
             curses_window
         }
     }
@@ -103,6 +117,10 @@ impl DrawScreen for CursesWindow {
         }
     }
 
+    fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
     fn get_cursor_line(&self) -> String {
         // Move the cursor to the beginning of the line
         // to get all the characters.
@@ -156,12 +174,22 @@ impl DrawScreen for CursesWindow {
                     }
                 }
 
-                ncurses::mvwaddstr(
-                    self.curses_window,
-                    self.position.y,
-                    self.position.x,
-                    &part.value
-                );
+                if self.wrap {
+                    ncurses::mvwaddstr(
+                        self.curses_window,
+                        self.position.y,
+                        self.position.x,
+                        &part.value
+                    );
+                } else {
+                    ncurses::mvwaddnstr(
+                        self.curses_window,
+                        self.position.y,
+                        self.position.x,
+                        &part.value,
+                        (self.width - self.position.x).max(0)
+                    );
+                }
                 self.position.x += part.value.len() as i32;
 
                 for style in &part.styles {
@@ -242,6 +270,14 @@ impl DrawScreen for CursesWindow {
         ncurses::wgetch(self.curses_window)
     }
 
+    fn poll_input(&self, timeout: Duration) -> Option<i32> {
+        ncurses::wtimeout(self.curses_window, timeout.as_millis() as i32);
+        let c = ncurses::wgetch(self.curses_window);
+        ncurses::wtimeout(self.curses_window, -1);
+
+        if c == ncurses::ERR { None } else { Some(c) }
+    }
+
     fn listen(&mut self) {
         loop {
             let c = ncurses::wgetch(self.curses_window);
@@ -0,0 +1,454 @@
+use std::any::Any;
+use std::mem;
+
+use gitko_common::ascii_table::{KEY_BTAB, KEY_HT};
+
+use crate::{handle_key, Component, KeyHandlers, Line, Part, Position, ScreenFactory, ScreenSize, Style, TaskResult, Window, RGB,
+            SPINNER_INTERVAL};
+
+// Borders around the layout and between its panes.
+const BORDER_HORIZONTAL: &str = "-";
+const BORDER_VERTICAL: &str = "|";
+const BORDER_CORNER: &str = "+";
+
+const FOCUSED_TITLE_COLORS: (RGB, RGB) = ((0, 0, 0), (0, 255, 255));
+const TITLE_COLORS: (RGB, RGB) = ((128, 128, 128), (0, 0, 0));
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    // Parts next to each other, from left to right.
+    Horizontal,
+    // Parts below each other, from top to bottom.
+    Vertical
+}
+
+// Components shown next to each other on the screen. Splits give their
+// parts space by ratio, e.g.
+//
+//     Layout::horizontal(vec![
+//         (1, Layout::pane("Status", &mut status)),
+//         (2, Layout::pane("Diff", &mut diff))
+//     ])
+//
+// shows the status in the left third and the diff in the rest.
+pub struct Layout<'a> {
+    node: Node<'a>
+}
+
+enum Node<'a> {
+    Pane(Box<dyn Pane + 'a>),
+    Split(Direction, Vec<(u32, Layout<'a>)>)
+}
+
+impl<'a> Layout<'a> {
+    pub fn pane<T: Component<T>>(title: &str, component: &'a mut T) -> Layout<'a> {
+        let mut key_handlers = KeyHandlers::new();
+        component.register_handlers(&mut key_handlers);
+
+        let pane = ComponentPane {
+            title: title.to_owned(),
+            component,
+            key_handlers
+        };
+
+        Layout { node: Node::Pane(Box::new(pane)) }
+    }
+
+    pub fn horizontal(parts: Vec<(u32, Layout<'a>)>) -> Layout<'a> {
+        Layout::split(Direction::Horizontal, parts)
+    }
+
+    pub fn vertical(parts: Vec<(u32, Layout<'a>)>) -> Layout<'a> {
+        Layout::split(Direction::Vertical, parts)
+    }
+
+    pub fn split(direction: Direction, parts: Vec<(u32, Layout<'a>)>) -> Layout<'a> {
+        Layout { node: Node::Split(direction, parts) }
+    }
+}
+
+// Lets the layout hold components of different types.
+trait Pane {
+    fn title(&self) -> &str;
+
+    fn start(&mut self, window: &mut Window);
+    fn keypress(&mut self, window: &mut Window, key: i32) -> bool;
+    fn render(&mut self, window: &mut Window) -> bool;
+    fn exit(&mut self, window: &mut Window);
+
+    fn task_done(&mut self, window: &mut Window, result: TaskResult);
+    fn message(&mut self, window: &mut Window, message: &dyn Any);
+}
+
+struct ComponentPane<'a, T: Component<T>> {
+    title: String,
+    component: &'a mut T,
+    key_handlers: KeyHandlers<T>
+}
+
+impl<T: Component<T>> Pane for ComponentPane<'_, T> {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn start(&mut self, window: &mut Window) {
+        self.component.on_start(window);
+    }
+
+    fn keypress(&mut self, window: &mut Window, key: i32) -> bool {
+        handle_key(self.component, &self.key_handlers, window, key)
+    }
+
+    fn render(&mut self, window: &mut Window) -> bool {
+        self.component.on_render(window)
+    }
+
+    fn exit(&mut self, window: &mut Window) {
+        self.component.on_exit(window);
+    }
+
+    fn task_done(&mut self, window: &mut Window, result: TaskResult) {
+        self.component.on_task_done(window, result);
+    }
+
+    fn message(&mut self, window: &mut Window, message: &dyn Any) {
+        self.component.on_pane_message(window, message);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32
+}
+
+impl Rect {
+    // Divides the rect by the weights, leaving `gap` columns or rows
+    // between the parts. The last part gets what rounding left over.
+    fn split(&self, direction: Direction, weights: &[u32], gap: i32) -> Vec<Rect> {
+        let total = match direction {
+            Direction::Horizontal => self.width,
+            Direction::Vertical   => self.height
+        };
+
+        let gaps = gap * (weights.len() as i32 - 1).max(0);
+        let available = (total - gaps).max(0);
+        let sum = weights.iter().sum::<u32>().max(1) as i32;
+
+        let mut offset = 0;
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, weight)| {
+                let size = if i == weights.len() - 1 { available - offset }
+                           else                      { available * *weight as i32 / sum };
+
+                let start = offset + gap * i as i32;
+                offset += size;
+
+                match direction {
+                    Direction::Horizontal => Rect { x: self.x + start, width: size, ..*self },
+                    Direction::Vertical   => Rect { y: self.y + start, height: size, ..*self }
+                }
+            })
+            .collect()
+    }
+}
+
+struct PaneWindow<'a> {
+    pane: Box<dyn Pane + 'a>,
+    title: Window,
+    window: Window
+}
+
+// Runs the components of a layout together. Keys go to the focused
+// pane, tab and shift + tab move the focus between the panes and
+// closing any of them closes the layout.
+pub struct LayoutRenderer<'a> {
+    panes: Vec<PaneWindow<'a>>,
+    borders: Vec<Window>,
+    focused: usize
+}
+
+impl<'a> LayoutRenderer<'a> {
+    pub fn new(
+        layout: Layout<'a>,
+        size: ScreenSize,
+        position: Position,
+        screen_factory: ScreenFactory) -> LayoutRenderer<'a> {
+        let mut renderer = LayoutRenderer {
+            panes: vec![],
+            borders: vec![],
+            focused: 0
+        };
+
+        // A frame around the whole layout, the panes go inside of it.
+        let Rect { x, y, width, height } = Rect { x: position.x, y: position.y, width: size.cols, height: size.lines };
+
+        renderer.place_border(Direction::Horizontal, Rect { x, y, width, height: 1 }, screen_factory);
+        renderer.place_border(Direction::Horizontal, Rect { x, y: y + height - 1, width, height: 1 }, screen_factory);
+        renderer.place_border(Direction::Vertical, Rect { x, y, width: 1, height }, screen_factory);
+        renderer.place_border(Direction::Vertical, Rect { x: x + width - 1, y, width: 1, height }, screen_factory);
+
+        let inside = Rect { x: x + 1, y: y + 1, width: (width - 2).max(1), height: (height - 2).max(1) };
+        renderer.place(layout, inside, screen_factory);
+
+        renderer
+    }
+
+    // Starts with the pane at the index focused, counting the panes
+    // from left to right and top to bottom as they were given.
+    pub fn focus(mut self, index: usize) -> LayoutRenderer<'a> {
+        self.focused = index.min(self.panes.len().saturating_sub(1));
+        self
+    }
+
+    fn place(&mut self, layout: Layout<'a>, rect: Rect, screen_factory: ScreenFactory) {
+        match layout.node {
+            Node::Pane(pane) => {
+                // The title takes the first row.
+                let title = Window::new(
+                    ScreenSize { lines: 1, cols: rect.width },
+                    Position { x: rect.x, y: rect.y },
+                    screen_factory
+                );
+
+                let window = Window::new(
+                    ScreenSize { lines: (rect.height - 1).max(1), cols: rect.width },
+                    Position { x: rect.x, y: rect.y + 1 },
+                    screen_factory
+                );
+
+                self.panes.push(PaneWindow { pane, title, window });
+            }
+            Node::Split(direction, parts) => {
+                let weights: Vec<u32> = parts.iter().map(|(w, _)| *w).collect();
+                let rects = rect.split(direction, &weights, 1);
+
+                for (i, ((_, part), part_rect)) in parts.into_iter().zip(rects).enumerate() {
+                    // The divider before the part reaches into the borders
+                    // around the split, its ends join them.
+                    if i > 0 {
+                        let Rect { x, y, width, height } = part_rect;
+
+                        let divider = match direction {
+                            Direction::Horizontal => Rect { x: x - 1, y: y - 1, width: 1, height: height + 2 },
+                            Direction::Vertical   => Rect { x: x - 1, y: y - 1, width: width + 2, height: 1 }
+                        };
+
+                        // Lines across a horizontal split are vertical.
+                        let line = match direction {
+                            Direction::Horizontal => Direction::Vertical,
+                            Direction::Vertical   => Direction::Horizontal
+                        };
+
+                        self.place_border(line, divider, screen_factory);
+                    }
+
+                    self.place(part, part_rect, screen_factory);
+                }
+            }
+        }
+    }
+
+    // A horizontal or vertical line filling the rect, with corners at
+    // both ends.
+    fn place_border(&mut self, direction: Direction, rect: Rect, screen_factory: ScreenFactory) {
+        let mut border = Window::new(
+            ScreenSize { lines: rect.height, cols: rect.width },
+            Position { x: rect.x, y: rect.y },
+            screen_factory
+        );
+        border.set_wrap(false);
+
+        let lines = match direction {
+            Direction::Horizontal => {
+                let inside = BORDER_HORIZONTAL.repeat((rect.width - 2).max(0) as usize);
+                vec![Line::plain(&format!("{}{}{}", BORDER_CORNER, inside, BORDER_CORNER))]
+            }
+            Direction::Vertical => {
+                let mut lines = vec![Line::plain(BORDER_VERTICAL); rect.height.max(2) as usize];
+                lines[0] = Line::plain(BORDER_CORNER);
+                lines[rect.height.max(2) as usize - 1] = Line::plain(BORDER_CORNER);
+                lines
+            }
+        };
+
+        border.set_lines(lines);
+        self.borders.push(border);
+    }
+
+    pub fn render(&mut self) {
+        if self.panes.is_empty() { return }
+
+        for border in &mut self.borders {
+            border.show_cursor(false);
+        }
+
+        for pane in &mut self.panes {
+            pane.title.show_cursor(false);
+            pane.window.clear();
+            pane.pane.start(&mut pane.window);
+        }
+
+        self.deliver_messages();
+        self.refresh();
+
+        loop {
+            let count = self.panes.len();
+
+            match self.next_key() {
+                KEY_HT   => self.focused = (self.focused + 1) % count,
+                KEY_BTAB => self.focused = (self.focused + count - 1) % count,
+                key => {
+                    let focused = &mut self.panes[self.focused];
+
+                    if !focused.pane.keypress(&mut focused.window, key) { break }
+                    if !focused.pane.render(&mut focused.window) { break }
+                }
+            }
+
+            self.deliver_messages();
+            self.refresh();
+        }
+
+        for pane in &mut self.panes {
+            pane.pane.exit(&mut pane.window);
+        }
+    }
+
+    // Same as Renderer::next_key, for the tasks of all panes.
+    fn next_key(&mut self) -> i32 {
+        loop {
+            let mut done = false;
+
+            for pane in &mut self.panes {
+                let results = pane.window.tasks.finished();
+                done |= !results.is_empty();
+
+                for result in results {
+                    pane.pane.task_done(&mut pane.window, result);
+                }
+            }
+
+            if done {
+                self.deliver_messages();
+                self.refresh();
+            }
+
+            let running = self.panes.iter().any(|p| p.window.tasks.is_running());
+            let senders = self.panes.iter().any(|p| p.window.tasks.has_senders());
+
+            let focused = &self.panes[self.focused].window;
+            if !running && !senders {
+                return focused.listen_input()
+            }
+
+            if let Some(key) = focused.poll_input(SPINNER_INTERVAL) {
+                return key
+            }
+
+            if running {
+                for pane in &mut self.panes {
+                    pane.window.tasks.frame += 1;
+                }
+
+                self.refresh();
+            }
+        }
+    }
+
+    // Hands what panes sent with Window::send_to_panes to the other
+    // panes, until none of them has anything more to say.
+    fn deliver_messages(&mut self) {
+        while let Some(sender) = self.panes.iter().position(|p| !p.window.outbox.is_empty()) {
+            let messages = mem::take(&mut self.panes[sender].window.outbox);
+
+            for message in messages {
+                for (i, pane) in self.panes.iter_mut().enumerate() {
+                    if i == sender { continue }
+
+                    pane.pane.message(&mut pane.window, message.as_ref());
+                }
+            }
+        }
+    }
+
+    // Everything is drawn again, windows opened from a pane may have
+    // covered the others.
+    fn refresh(&mut self) {
+        for border in &mut self.borders {
+            border.queue_update();
+            border.refresh();
+        }
+
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            let title = format!(" {} ", pane.pane.title());
+
+            // Colors are lost on ncurses, the focused title is bold as well.
+            let part = if i == self.focused {
+                let (foreground, background) = FOCUSED_TITLE_COLORS;
+                Part::new(&title, Some(vec![Style::Bold, Style::Painted(foreground, background)]))
+            } else {
+                let (foreground, background) = TITLE_COLORS;
+                Part::painted(&title, foreground, background)
+            };
+
+            pane.title.set_lines(vec![Line::new(vec![part])]);
+            pane.title.queue_update();
+            pane.title.refresh();
+        }
+
+        // The focused pane goes last, so the terminal cursor ends up in it.
+        let focused = self.focused;
+        let order = (0..self.panes.len()).filter(|i| *i != focused).chain([focused]);
+
+        for i in order {
+            let window = &mut self.panes[i].window;
+            window.queue_update();
+            window.refresh();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, Rect};
+
+    fn sizes(rects: &[Rect], direction: Direction) -> Vec<(i32, i32)> {
+        rects
+            .iter()
+            .map(|r| match direction {
+                Direction::Horizontal => (r.x, r.width),
+                Direction::Vertical   => (r.y, r.height)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_divides_by_weight_with_gaps() {
+        let rect = Rect { x: 1, y: 1, width: 101, height: 20 };
+        let rects = rect.split(Direction::Horizontal, &[2, 3], 1);
+
+        assert_eq!(sizes(&rects, Direction::Horizontal), vec![(1, 40), (42, 60)]);
+        assert!(rects.iter().all(|r| r.y == 1 && r.height == 20));
+    }
+
+    #[test]
+    fn split_gives_the_rest_to_the_last_part() {
+        let rect = Rect { x: 0, y: 0, width: 10, height: 10 };
+        let rects = rect.split(Direction::Vertical, &[1, 1, 1], 0);
+
+        assert_eq!(sizes(&rects, Direction::Vertical), vec![(0, 3), (3, 3), (6, 4)]);
+    }
+
+    #[test]
+    fn split_never_gives_negative_sizes() {
+        let rect = Rect { x: 0, y: 0, width: 1, height: 1 };
+        let rects = rect.split(Direction::Horizontal, &[1, 1], 1);
+
+        assert!(rects.iter().all(|r| r.width >= 0));
+    }
+}
@@ -1,12 +1,27 @@
 #![allow(dead_code)]
 
+use std::any::Any;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
 
 use gitko_common::ascii_table::*;
 
+mod layout;
+
+pub use layout::{Direction, Layout, LayoutRenderer};
+
 pub type KeyHandlers<T> = HashMap<i32, fn(&mut T, &mut Window) -> bool>;
 pub type ScreenFactory = fn(ScreenSize, Position) -> Box<dyn DrawScreen>;
+pub type TaskResult = Box<dyn Any + Send>;
+
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+// How often the spinner moves while tasks are running.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct Renderer<'a, T: Component<T>>  {
     key_handlers: KeyHandlers<T>,
@@ -43,7 +58,7 @@ impl<'a, T: Component<T>> Renderer<'a, T> {
             self.refresh();
             if !self.component.on_render(&mut self.window) { break; }
 
-            c = self.window.listen_input();
+            c = self.next_key();
         }
 
         self.component.on_exit(&mut self.window);
@@ -54,42 +69,115 @@ impl<'a, T: Component<T>> Renderer<'a, T> {
         self.refresh();
    }
 
-    fn on_keypress(&mut self, c: i32) -> bool {
-        if let Some(handler) = self.key_handlers.get(&c) {
-            return handler(self.component, &mut self.window)
-        } else {
-            match c {
-                KEY_J_LOWER => self.window.move_cursor_down(),
-                KEY_K_LOWER => self.window.move_cursor_up(),
-                KEY_Q_LOWER => return false,
-                4 => { // 4 == EOT end of transmission - a very dirty hack to get ctrl + d
-                    for _ in 0..20 {
-                        self.window.move_cursor_down();
-                    }
-                },
-                21 => { // 21 == NAK negative acknowledge - a very dirty hack to get ctrl + u
-                    for _ in 0..20 {
-                        self.window.move_cursor_up();
-                    }
-                }
-                _ => {}
+    // Gives access to the component between draws, e.g. to append
+    // the output of a process which is still running.
+    pub fn component(&mut self) -> &mut T {
+        self.component
+    }
+
+    // Waits at most `timeout` for a key, so work like reading the output
+    // of a process can go on between keys.
+    pub fn poll_input(&mut self, timeout: Duration) -> Option<i32> {
+        self.window.poll_input(timeout)
+    }
+
+    // Same as draw, but scrolls to the last lines of the component.
+    pub fn draw_to_end(&mut self) {
+        self.component.on_start(&mut self.window);
+        self.window.scroll_to_end();
+        self.refresh();
+    }
+
+    // Waits for the next key, handing results of background tasks to
+    // the component while waiting.
+    fn next_key(&mut self) -> i32 {
+        loop {
+            let results = self.window.tasks.finished();
+            let done = !results.is_empty();
+
+            for result in results {
+                self.component.on_task_done(&mut self.window, result);
+            }
+
+            if done {
+                self.refresh();
+            }
+
+            let running = self.window.tasks.is_running();
+            if !running && !self.window.tasks.has_senders() {
+                return self.window.listen_input()
+            }
+
+            if let Some(key) = self.window.poll_input(SPINNER_INTERVAL) {
+                return key
+            }
+
+            if running {
+                self.window.tasks.frame += 1;
+                self.refresh();
             }
         }
+    }
 
-        true
+    fn on_keypress(&mut self, c: i32) -> bool {
+        handle_key(self.component, &self.key_handlers, &mut self.window, c)
     }
 
     fn refresh(&mut self) {
+        // Without a layout there are no other panes to send to.
+        self.window.outbox.clear();
+
         self.window.queue_update();
         self.window.refresh();
     }
 }
 
+// Passes the key to its handler or the component, keys neither of them
+// take move the cursor. Returns false when the component should close.
+fn handle_key<T: Component<T>>(component: &mut T, handlers: &KeyHandlers<T>, window: &mut Window, c: i32) -> bool {
+    if let Some(handler) = handlers.get(&c) {
+        return handler(component, window)
+    } else if let Some(result) = component.on_input(window, c) {
+        return result
+    } else {
+        match c {
+            KEY_J_LOWER => window.move_cursor_down(),
+            KEY_K_LOWER => window.move_cursor_up(),
+            KEY_Q_LOWER => return false,
+            4 => { // 4 == EOT end of transmission - a very dirty hack to get ctrl + d
+                for _ in 0..20 {
+                    window.move_cursor_down();
+                }
+            },
+            21 => { // 21 == NAK negative acknowledge - a very dirty hack to get ctrl + u
+                for _ in 0..20 {
+                    window.move_cursor_up();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    true
+}
+
 pub trait Component<T: Component<T>> {
     fn on_start(&mut self, _window: &mut Window) { }
     fn on_render(&mut self, _window: &mut Window) -> bool { true }
     fn on_exit(&mut self, _window: &mut Window) { }
 
+    // Called with the result of a task started with Window::spawn_task,
+    // once it is done.
+    fn on_task_done(&mut self, _window: &mut Window, _result: TaskResult) { }
+
+    // Called for keys without a registered handler, before the default
+    // movement keys. Returning None falls back to those.
+    fn on_input(&mut self, _window: &mut Window, _key: i32) -> Option<bool> { None }
+
+    // Called with what other panes of the layout sent with
+    // Window::send_to_panes, e.g. the item under their cursor.
+    fn on_pane_message(&mut self, _window: &mut Window, _message: &dyn Any) { }
+
     fn register_handlers(&self, _handlers: &mut KeyHandlers<T>) { }
 }
 
@@ -101,7 +189,11 @@ pub struct Window {
     pub cursor_position: Position,
 
     cursor_hidden: bool,
-    screen: Box<dyn DrawScreen>
+    screen: Box<dyn DrawScreen>,
+
+    tasks: Tasks,
+    // Messages for the other panes of the layout.
+    outbox: Vec<Box<dyn Any>>
 }
 
 impl Window {
@@ -113,12 +205,94 @@ impl Window {
             position: Position::default(),
             cursor_position: Position::default(),
             cursor_hidden: false,
-            screen: screen_factory(size, position)
+            screen: screen_factory(size, position),
+
+            tasks: Tasks::new(),
+            outbox: vec![]
         }
     }
 
+    // Runs the work on another thread, the result is passed to
+    // Component::on_task_done. A spinner with the description is shown
+    // in the bottom row of the window until then.
+    pub fn spawn_task<R: Send + 'static>(&mut self, description: &str, work: impl FnOnce() -> R + Send + 'static) {
+        self.tasks.spawn(description, work);
+    }
+
+    pub fn has_running_tasks(&self) -> bool {
+        self.tasks.is_running()
+    }
+
+    // For threads which keep running and send more than one result,
+    // e.g. one watching files. Results are passed to
+    // Component::on_task_done, without a spinner.
+    pub fn task_sender(&self) -> TaskSender {
+        TaskSender {
+            sender: self.tasks.sender.clone(),
+            _alive: self.tasks.senders.clone()
+        }
+    }
+
+    // Passed to Component::on_pane_message of the other panes once the
+    // current key is handled. Outside of a layout it is dropped.
+    pub fn send_to_panes<M: Any>(&mut self, message: M) {
+        self.outbox.push(Box::new(message));
+    }
+
+
+    // When the line under the cursor has a key, the cursor follows it to
+    // where it is in the new lines. If it is gone the cursor moves to the
+    // nearest keyed line around it which is still there.
     pub fn set_lines(&mut self, lines: Vec<Line>) {
+        let index = self.cursor_line_index();
+        let target = self.find_cursor_item(index, &lines);
+
         self.lines = lines;
+        if self.lines.is_empty() { return }
+
+        let target = target.unwrap_or(index).min(self.lines.len() - 1);
+        if target != index || self.screen_start >= self.lines.len() {
+            self.keep_row_on_line(target);
+        }
+    }
+
+    fn find_cursor_item(&self, index: usize, lines: &[Line]) -> Option<usize> {
+        let current = self.lines.get(index).filter(|l| l.key.is_some())?;
+
+        // The first line with a key wins, like a search from the top.
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        for (i, key) in lines.iter().enumerate().filter_map(|(i, l)| Some((i, l.key.as_deref()?))) {
+            positions.entry(key).or_insert(i);
+        }
+
+        let find = |line: &Line| positions.get(line.key.as_deref()?).copied();
+
+        if let Some(found) = find(current) { return Some(found) }
+
+        // The item after a removed one takes its place, so the lines
+        // below are tried first.
+        (1..self.lines.len()).find_map(|distance| {
+            let after = self.lines.get(index + distance).and_then(find);
+            let before = index.checked_sub(distance).and_then(|i| self.lines.get(i)).and_then(find);
+
+            after.or(before)
+        })
+    }
+
+    // Moves the cursor to the line at the index, keeping the cursor on
+    // the same row of the screen if the lines allow it.
+    fn keep_row_on_line(&mut self, index: usize) {
+        let row = self.cursor_position.y.max(0) as usize;
+        let height = self.screen.height() as usize;
+
+        self.screen_start = index
+            .saturating_sub(row)
+            .min(self.lines.len().saturating_sub(height));
+
+        let (_, position) = self.screen.move_cursor(
+            Position { x: self.cursor_position.x, y: (index - self.screen_start) as i32 }
+        );
+        self.cursor_position = position;
     }
 
     pub fn lines(&self) -> Vec<Line> {
@@ -130,14 +304,33 @@ impl Window {
     }
 
     fn queue_update(&mut self) {
+        let status = self.tasks.status_line();
+
+        // The spinner gets the bottom row to itself, the lines end above
+        // it and the cursor line is scrolled up out from under it.
         let lines  = self.lines.len();
-        let height = self.screen.height() as usize;
+        let height = (self.screen.height() as usize).saturating_sub(status.is_some() as usize);
+
+        if status.is_some() && height > 0 && self.cursor_position.y as usize >= height {
+            let shift = self.cursor_position.y as usize + 1 - height;
+            self.screen_start += shift;
+
+            let (_, position) = self.screen.move_cursor(
+                Position { x: self.cursor_position.x, y: self.cursor_position.y - shift as i32 }
+            );
+            self.cursor_position = position;
+        }
 
-        let start = self.screen_start;
-        let end   = height + self.screen_start;
+        let start = self.screen_start.min(lines);
+        let end   = height + start;
         let end   = if end < lines { end } else { lines };
 
-        let data  = self.lines[start..end].to_vec();
+        let mut data = self.lines[start..end].to_vec();
+
+        if let Some(status) = status {
+            data.resize(height, Line::empty());
+            data.push(status);
+        }
 
         self.screen.set_data(data);
         self.screen.queue_update();
@@ -148,6 +341,10 @@ impl Window {
         self.screen.listen_input()
     }
 
+    pub fn poll_input(&self, timeout: Duration) -> Option<i32> {
+        self.screen.poll_input(timeout)
+    }
+
     // TODO: think about listening for input outside of rendering methods
     pub fn listen(&mut self) {
         self.screen.listen()
@@ -172,6 +369,26 @@ impl Window {
         self.screen.get_cursor_line()
     }
 
+    // The whole line under the cursor, the row on the screen may be cut
+    // off or wrapped and is not drawn yet right after set_lines.
+    pub fn cursor_line_value(&self) -> String {
+        self.lines
+            .get(self.cursor_line_index())
+            .map(|l| l.value())
+            .unwrap_or_default()
+    }
+
+    // Wrapped lines take more than one row, which the cursor does not
+    // know about. Lists the cursor moves over are better cut off.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.screen.set_wrap(wrap);
+    }
+
+    // Index of the cursor line in `lines`.
+    pub fn cursor_line_index(&self) -> usize {
+        self.screen_start + self.cursor_position.y as usize
+    }
+
     fn set_cursor(&mut self, position: Position) {
         let current_position = self.cursor_position.y + self.screen_start as i32;
 
@@ -223,6 +440,36 @@ impl Window {
         self.queue_update();
     }
 
+    // Moves the cursor to the line at the index, scrolling so that
+    // the line ends up in the middle of the screen when possible.
+    pub fn move_to_line(&mut self, index: usize) {
+        if self.lines.is_empty() { return }
+
+        let height = self.screen.height() as usize;
+        let index = index.min(self.lines.len() - 1);
+
+        self.screen_start = index
+            .saturating_sub(height / 2)
+            .min(self.lines.len().saturating_sub(height));
+
+        let (_, position) = self.screen.move_cursor(
+            Position { x: 0, y: (index - self.screen_start) as i32 }
+        );
+        self.cursor_position = position;
+    }
+
+    pub fn move_to_column(&mut self, column: usize) {
+        let (_, position) = self.screen.move_cursor(
+            Position { x: column as i32, y: self.cursor_position.y }
+        );
+        self.cursor_position = position;
+    }
+
+    pub fn scroll_to_end(&mut self) {
+        let height = self.screen.height() as usize;
+        self.screen_start = self.lines.len().saturating_sub(height);
+    }
+
     pub fn move_screen_up(&mut self, delta: usize) {
         if (self.screen_start as i32 - delta as i32 ) < 0 { return; }
 
@@ -301,6 +548,103 @@ impl Window {
     }
 }
 
+enum TaskMessage {
+    // Id of the task, without a result when it panicked.
+    Done(usize, Option<TaskResult>),
+    Sent(TaskResult)
+}
+
+#[derive(Clone)]
+pub struct TaskSender {
+    sender: Sender<TaskMessage>,
+    // Counts the senders, input is polled while there are any.
+    _alive: Arc<()>
+}
+
+impl TaskSender {
+    // Returns false once the window is gone.
+    pub fn send<R: Send + 'static>(&self, result: R) -> bool {
+        self.sender.send(TaskMessage::Sent(Box::new(result))).is_ok()
+    }
+}
+
+struct Tasks {
+    sender: Sender<TaskMessage>,
+    receiver: Receiver<TaskMessage>,
+    senders: Arc<()>,
+    next_id: usize,
+    // Ids and descriptions of the tasks which are not done yet.
+    running: Vec<(usize, String)>,
+    frame: usize
+}
+
+impl Tasks {
+    fn new() -> Tasks {
+        let (sender, receiver) = mpsc::channel();
+
+        Tasks {
+            sender,
+            receiver,
+            senders: Arc::new(()),
+            next_id: 0,
+            running: vec![],
+            frame: 0
+        }
+    }
+
+    fn spawn<R: Send + 'static>(&mut self, description: &str, work: impl FnOnce() -> R + Send + 'static) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running.push((id, description.to_owned()));
+
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            // A task which panics is still done, without a result.
+            let result = panic::catch_unwind(AssertUnwindSafe(work))
+                .ok()
+                .map(|r| Box::new(r) as TaskResult);
+
+            let _ = sender.send(TaskMessage::Done(id, result));
+        });
+    }
+
+    fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    fn has_senders(&self) -> bool {
+        Arc::strong_count(&self.senders) > 1
+    }
+
+    fn finished(&mut self) -> Vec<TaskResult> {
+        let mut results = vec![];
+
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                TaskMessage::Done(id, result) => {
+                    self.running.retain(|(i, _)| *i != id);
+                    results.extend(result);
+                }
+                TaskMessage::Sent(result) => results.push(result)
+            }
+        }
+
+        results
+    }
+
+    fn status_line(&self) -> Option<Line> {
+        let (_, description) = self.running.last()?;
+        let spinner = SPINNER_FRAMES[self.frame % SPINNER_FRAMES.len()];
+
+        let text = match self.running.len() {
+            1 => format!("{} {}", spinner, description),
+            n => format!("{} {} (+{} more)", spinner, description, n - 1)
+        };
+
+        Some(Line::new(vec![Part::painted(&text, (0, 255, 255), (0, 0, 0))]))
+    }
+}
+
 pub trait DrawScreen {
     fn set_data(&mut self, lines: Vec<Line>);
 
@@ -312,6 +656,10 @@ pub trait DrawScreen {
     fn show_cursor(&mut self, show: bool);
     fn get_cursor_line(&self) -> String;
 
+    // Lines wider than the screen continue on the next row unless
+    // wrapping is turned off, then they are cut off.
+    fn set_wrap(&mut self, wrap: bool);
+
     fn queue_update(&mut self);
     fn refresh(&mut self);
     fn clear(&mut self);
@@ -325,6 +673,8 @@ pub trait DrawScreen {
     fn set_cursor(&mut self, position: Position);
 
     fn listen_input(&self) -> i32;
+    // Same as listen_input, but gives up after the timeout.
+    fn poll_input(&self, timeout: Duration) -> Option<i32>;
     fn listen(&mut self);
 }
 
@@ -372,12 +722,20 @@ impl Part {
 
 #[derive(Clone)]
 pub struct Line {
-    pub parts: Vec<Part>
+    pub parts: Vec<Part>,
+    // Identifies the item shown on the line, like a file or a commit,
+    // so Window::set_lines can keep the cursor on it.
+    pub key: Option<String>
 }
 
 impl Line {
     pub fn new(parts: Vec<Part>) -> Line {
-        Line { parts }
+        Line { parts, key: None }
+    }
+
+    pub fn with_key(mut self, key: &str) -> Line {
+        self.key = Some(key.to_owned());
+        self
     }
 
     pub fn plain(value: &str) -> Line {
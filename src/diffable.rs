@@ -0,0 +1,90 @@
+use crate::git::DiffOptions;
+use gitko_render::{KeyHandlers, Window, Component, Line, Part, Style};
+
+use gitko_common::ascii_table::{KEY_A_LOWER, KEY_B_LOWER, KEY_C_LOWER, KEY_MINUS, KEY_PLUS, KEY_R_LOWER, KEY_W_LOWER};
+
+const MAX_CONTEXT_LINES: u32 = 100;
+const RENAME_THRESHOLDS: [Option<u32>; 4] = [Some(50), Some(75), Some(90), None];
+
+pub trait DiffableComponent<T: DiffableComponent<T> + Component<T>>: Component<T> {
+    fn diff_options(&mut self) -> &mut DiffOptions;
+
+    fn toggle_ignore_whitespace(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+        options.ignore_whitespace = !options.ignore_whitespace;
+
+        self.on_start(window);
+        true
+    }
+
+    fn toggle_ignore_blank_lines(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+        options.ignore_blank_lines = !options.ignore_blank_lines;
+
+        self.on_start(window);
+        true
+    }
+
+    fn increase_context(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+        options.context_lines = (options.context_lines + 1).min(MAX_CONTEXT_LINES);
+
+        self.on_start(window);
+        true
+    }
+
+    fn decrease_context(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+        options.context_lines = options.context_lines.saturating_sub(1);
+
+        self.on_start(window);
+        true
+    }
+
+    fn next_algorithm(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+        options.algorithm = options.algorithm.next();
+
+        self.on_start(window);
+        true
+    }
+
+    fn next_rename_threshold(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+
+        let next = RENAME_THRESHOLDS
+            .iter()
+            .position(|t| *t == options.rename_threshold)
+            .map(|p| (p + 1) % RENAME_THRESHOLDS.len())
+            .unwrap_or(0);
+        options.rename_threshold = RENAME_THRESHOLDS[next];
+
+        self.on_start(window);
+        true
+    }
+
+    fn toggle_copy_detection(&mut self, window: &mut Window) -> bool {
+        let options = self.diff_options();
+        options.detect_copies = !options.detect_copies;
+
+        self.on_start(window);
+        true
+    }
+}
+
+pub fn diff_options_header(options: &DiffOptions) -> Line {
+    Line::new(vec![
+        Part::new("Diff:", Some(vec![Style::Bold, Style::Underlined])),
+        Part::plain(&format!(" {}", options.describe()))
+    ])
+}
+
+pub fn register_diff_option_handlers<T: DiffableComponent<T>>(handlers: &mut KeyHandlers<T>) {
+    handlers.insert(KEY_W_LOWER, DiffableComponent::toggle_ignore_whitespace);
+    handlers.insert(KEY_B_LOWER, DiffableComponent::toggle_ignore_blank_lines);
+    handlers.insert(KEY_PLUS, DiffableComponent::increase_context);
+    handlers.insert(KEY_MINUS, DiffableComponent::decrease_context);
+    handlers.insert(KEY_A_LOWER, DiffableComponent::next_algorithm);
+    handlers.insert(KEY_R_LOWER, DiffableComponent::next_rename_threshold);
+    handlers.insert(KEY_C_LOWER, DiffableComponent::toggle_copy_detection);
+}
@@ -0,0 +1,33 @@
+use std::env;
+use std::process::Command;
+
+use crate::{suspend, resume};
+
+const DEFAULT_EDITOR: &str = "vi";
+
+// Opens the file in $VISUAL or $EDITOR at the given line, starting at 1.
+// The terminal is handed over to the editor until it exits.
+pub fn open_in_editor(path: &str, line: Option<usize>) {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_owned());
+
+    // The variable may contain arguments, like 'code --wait'.
+    let mut words = editor.split_whitespace();
+    let Some(program) = words.next() else { return };
+
+    let mut command = Command::new(program);
+    command.args(words);
+
+    if let Some(line) = line {
+        command.arg(format!("+{}", line));
+    }
+
+    command.arg(path);
+
+    suspend();
+    let _ = command.status();
+    resume();
+}
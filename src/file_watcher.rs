@@ -0,0 +1,197 @@
+use gitko_render::TaskSender;
+
+#[cfg(feature = "file-watching")]
+use std::fs;
+#[cfg(feature = "file-watching")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "file-watching")]
+use std::sync::mpsc::{self, RecvTimeoutError};
+#[cfg(feature = "file-watching")]
+use std::sync::{Arc, Mutex, Weak};
+#[cfg(feature = "file-watching")]
+use std::thread;
+#[cfg(feature = "file-watching")]
+use std::time::Duration;
+
+#[cfg(feature = "file-watching")]
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+#[cfg(feature = "file-watching")]
+use crate::git;
+
+// Changes are reported once nothing changed for this long, a checkout
+// or a build touches many files in a row.
+#[cfg(feature = "file-watching")]
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Sent to the window once files changed.
+pub struct FilesChanged;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WatchScope {
+    // The worktree and the git directory, for the status.
+    Worktree,
+    // Only the git directory, for refs and HEAD.
+    GitDir
+}
+
+// Watches the repository while it is alive. Without the 'file-watching'
+// feature nothing is watched.
+pub struct FileWatcher {
+    // The repository being watched, a different one after switching
+    // worktrees needs a new watcher.
+    pub root: String,
+    #[cfg(feature = "file-watching")]
+    _watcher: Arc<Mutex<RecommendedWatcher>>
+}
+
+impl FileWatcher {
+    #[cfg(not(feature = "file-watching"))]
+    pub fn new(_scope: WatchScope, _sender: TaskSender) -> Option<FileWatcher> {
+        None
+    }
+
+    #[cfg(feature = "file-watching")]
+    pub fn new(scope: WatchScope, sender: TaskSender) -> Option<FileWatcher> {
+        let root = git::toplevel()?;
+        let git_dir = PathBuf::from(git::git_dir()?);
+
+        let (events, received) = mpsc::channel::<Vec<PathBuf>>();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+
+            // Reads, like the ones of git itself, are not changes.
+            if matches!(event.kind, EventKind::Access(_)) { return }
+
+            let _ = events.send(event.paths);
+        }).ok()?;
+
+        // The git directory, which is somewhere else for a linked
+        // worktree, is left out and watched on its own.
+        if scope == WatchScope::Worktree {
+            watcher.watch(Path::new(&root), RecursiveMode::NonRecursive).ok()?;
+            watch_directories(&mut watcher, &root, &git_dir, PathBuf::from(&root));
+        }
+
+        watcher.watch(&git_dir, RecursiveMode::Recursive).ok()?;
+
+        let watcher = Arc::new(Mutex::new(watcher));
+        let (weak, thread_root) = (Arc::downgrade(&watcher), root.clone());
+        thread::spawn(move || debounce(received, weak, thread_root, git_dir, sender));
+
+        Some(FileWatcher { root, _watcher: watcher })
+    }
+}
+
+// Watches the directories below `directory` one by one, skipping the
+// ignored ones. Build output or dependencies like 'target' or
+// 'node_modules' would use up the watches the system allows otherwise.
+#[cfg(feature = "file-watching")]
+fn watch_directories(watcher: &mut RecommendedWatcher, root: &str, git_dir: &Path, directory: PathBuf) {
+    let mut pending = vec![directory];
+
+    while let Some(directory) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else { continue };
+
+        let children: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()) && e.file_name() != ".git")
+            .map(|e| e.path().to_string_lossy().to_string())
+            .collect();
+        if children.is_empty() { continue }
+
+        let ignored = git::ignored_paths(root, &children);
+
+        for child in children.into_iter().filter(|c| !ignored.contains(c)) {
+            let child = PathBuf::from(child);
+            if child.starts_with(git_dir) { continue }
+
+            // Past the limit of watches the rest stays unwatched.
+            if watcher.watch(&child, RecursiveMode::NonRecursive).is_ok() {
+                pending.push(child);
+            }
+        }
+    }
+}
+
+// Collects changes until it is quiet and sends FilesChanged if any of
+// them matters. Ends once the watcher or the window is gone.
+#[cfg(feature = "file-watching")]
+fn debounce(
+    received: mpsc::Receiver<Vec<PathBuf>>,
+    watcher: Weak<Mutex<RecommendedWatcher>>,
+    root: String,
+    git_dir: PathBuf,
+    sender: TaskSender) {
+    while let Ok(first) = received.recv() {
+        let mut paths = first;
+
+        loop {
+            match received.recv_timeout(DEBOUNCE) {
+                Ok(more)                            => paths.extend(more),
+                Err(RecvTimeoutError::Timeout)      => break,
+                Err(RecvTimeoutError::Disconnected) => return
+            }
+        }
+
+        let Some(watcher) = watcher.upgrade() else { return };
+        watch_new_directories(&watcher, &root, &git_dir, &paths);
+
+        if is_relevant(&paths, &root, &git_dir) && !sender.send(FilesChanged) {
+            return
+        }
+    }
+}
+
+// Directories created after the watcher started are watched too.
+#[cfg(feature = "file-watching")]
+fn watch_new_directories(watcher: &Mutex<RecommendedWatcher>, root: &str, git_dir: &Path, paths: &[PathBuf]) {
+    let mut directories: Vec<String> = paths
+        .iter()
+        .filter(|p| p.starts_with(root) && !p.starts_with(git_dir) && p.is_dir())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    directories.sort();
+    directories.dedup();
+    if directories.is_empty() { return }
+
+    let ignored = git::ignored_paths(root, &directories);
+    let Ok(mut watcher) = watcher.lock() else { return };
+
+    for directory in directories.into_iter().filter(|d| !ignored.contains(d)) {
+        let directory = PathBuf::from(directory);
+
+        if watcher.watch(&directory, RecursiveMode::NonRecursive).is_ok() {
+            watch_directories(&mut watcher, root, git_dir, directory);
+        }
+    }
+}
+
+#[cfg(feature = "file-watching")]
+fn is_relevant(paths: &[PathBuf], root: &str, git_dir: &Path) -> bool {
+    let (in_git_dir, in_worktree): (Vec<&PathBuf>, Vec<&PathBuf>) = paths
+        .iter()
+        .partition(|p| p.starts_with(git_dir));
+
+    // Objects are written before the refs pointing to them, lock files
+    // come and go with every command.
+    let git_dir_changed = in_git_dir.iter().any(|p| {
+        !p.starts_with(git_dir.join("objects"))
+            && p.extension().is_none_or(|e| e != "lock")
+            && p.file_name().is_none_or(|n| n != "GITKO_EDITMSG")
+    });
+
+    if git_dir_changed { return true }
+    if in_worktree.is_empty() { return false }
+
+    // Build output and the like is ignored.
+    let mut worktree_paths: Vec<String> = in_worktree
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    worktree_paths.sort();
+    worktree_paths.dedup();
+
+    git::ignored_paths(root, &worktree_paths).len() < worktree_paths.len()
+}
@@ -1,6 +1,14 @@
 #![allow(dead_code)]
+use std::io::Write;
 use std::path::Path;
+use std::process::Stdio;
 
+use crate::process;
+use crate::process::{CommandOutput, Update};
+use crate::undo;
+use crate::undo::Operation;
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum FileState {
     Invalid,
     Unknown,
@@ -70,6 +78,115 @@ pub fn parse_file_state(path: &str) -> FileState {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+    Histogram,
+    Minimal
+}
+
+impl DiffAlgorithm {
+    pub fn name(&self) -> &str {
+        match self {
+            DiffAlgorithm::Myers     => "myers",
+            DiffAlgorithm::Patience  => "patience",
+            DiffAlgorithm::Histogram => "histogram",
+            DiffAlgorithm::Minimal   => "minimal"
+        }
+    }
+
+    pub fn next(&self) -> DiffAlgorithm {
+        match self {
+            DiffAlgorithm::Myers     => DiffAlgorithm::Patience,
+            DiffAlgorithm::Patience  => DiffAlgorithm::Histogram,
+            DiffAlgorithm::Histogram => DiffAlgorithm::Minimal,
+            DiffAlgorithm::Minimal   => DiffAlgorithm::Myers
+        }
+    }
+}
+
+// Options passed to every command producing a diff, so all of them
+// show the same thing.
+#[derive(Clone)]
+pub struct DiffOptions {
+    pub ignore_whitespace: bool,
+    pub ignore_blank_lines: bool,
+    pub context_lines: u32,
+    pub algorithm: DiffAlgorithm,
+    // Similarity percentage for rename detection, None turns it off.
+    pub rename_threshold: Option<u32>,
+    pub detect_copies: bool
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            context_lines: 3,
+            algorithm: DiffAlgorithm::Myers,
+            rename_threshold: Some(50),
+            detect_copies: false
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("-U{}", self.context_lines),
+            format!("--diff-algorithm={}", self.algorithm.name())
+        ];
+
+        if self.ignore_whitespace  { args.push("-w".to_owned()); }
+        if self.ignore_blank_lines { args.push("--ignore-blank-lines".to_owned()); }
+
+        match self.rename_threshold {
+            Some(threshold) => {
+                args.push(format!("-M{}%", threshold));
+
+                if self.detect_copies {
+                    args.push(format!("-C{}%", threshold));
+                }
+            }
+            None => args.push("--no-renames".to_owned())
+        }
+
+        args
+    }
+
+    pub fn describe(&self) -> String {
+        let renames = match self.rename_threshold {
+            Some(threshold) => format!("{}%", threshold),
+            None            => "off".to_owned()
+        };
+
+        let toggle = |on: bool| if on { "on" } else { "off" };
+
+        format!(
+            "context: {}  ignore whitespace: {}  ignore blank lines: {}  algorithm: {}  renames: {}  copies: {}",
+            self.context_lines,
+            toggle(self.ignore_whitespace),
+            toggle(self.ignore_blank_lines),
+            self.algorithm.name(),
+            renames,
+            toggle(self.detect_copies && self.rename_threshold.is_some())
+        )
+    }
+}
+
+// Runs git with the diff options inserted after the leading arguments.
+fn run_diff(args: Vec<&str>, options: &DiffOptions, rest: Vec<&str>) -> Vec<String> {
+    let option_args = options.args();
+
+    let mut all_args = args;
+    all_args.extend(option_args.iter().map(String::as_str));
+    all_args.extend(rest);
+
+    run(all_args)
+}
+
 pub fn is_ignored(path: &Path) -> bool {
     let path_str = path.to_str().unwrap();
     let output = run(vec!["check-ignore", path_str]);
@@ -84,6 +201,13 @@ pub fn current_branch() -> String {
         .clone()
 }
 
+pub fn rev_parse(revision: &str) -> Option<String> {
+    run(vec!["rev-parse", "--verify", "--quiet", revision])
+        .first()
+        .filter(|l| !l.is_empty())
+        .cloned()
+}
+
 pub fn last_origin_commit_hash() -> String {
     run(vec!["rev-parse", &format!("origin/{}", &current_branch())])
         .first()
@@ -127,16 +251,422 @@ pub fn head_branch() -> String {
         .clone()
 }
 
+// Without optional locks status does not write the refreshed index,
+// which would wake up the file watcher again.
 pub fn status() -> Vec<String> {
-    run(vec!["status", "-s"])
+    run(vec!["--no-optional-locks", "status", "-s"])
+}
+
+// The paths which are ignored by .gitignore and friends.
+// Runs in `root`, the working directory changes while a nested session
+// or another worktree is open.
+pub fn ignored_paths(root: &str, paths: &[String]) -> Vec<String> {
+    let child = std::process::Command::new("git")
+        .args(["-C", root, "check-ignore", "-z", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let Ok(mut child) = child else { return vec![] };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(paths.join("\0").as_bytes());
+    }
+
+    let Ok(output) = child.wait_with_output() else { return vec![] };
+
+    String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+// Submodules are shown as the commits between the two recorded
+// hashes instead of a bare 'Subproject commit' line.
+pub fn diff_file(path: &str, options: &DiffOptions) -> Vec<String> {
+    run_diff(vec!["--no-pager", "diff", "--submodule=log"], options, vec!["--", path])
+}
+
+pub fn diff_staged_file(path: &str, options: &DiffOptions) -> Vec<String> {
+    run_diff(vec!["--no-pager", "diff", "--cached", "--submodule=log"], options, vec!["--", path])
+}
+
+// Files with changes both in the index and in the worktree, like 'MM'.
+pub fn is_partially_staged(path: &str) -> bool {
+    run(vec!["status", "-s", "--", path])
+        .first()
+        .filter(|l| l.len() > 3)
+        .map(|l| {
+            let (first, second) = parse_status(l);
+            first != ' ' && first != '?' && second != ' '
+        })
+        .unwrap_or(false)
+}
+
+// Size in bytes of an object like 'HEAD:path' or ':path' for the index,
+// None when the object does not exist.
+pub fn object_size(object: &str) -> Option<u64> {
+    let output = std::process::Command::new("git")
+        .args(["cat-file", "-s", object])
+        .output()
+        .ok()?;
+
+    if !output.status.success() { return None }
+
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+// Raw content of an object, binary files are not valid UTF-8.
+pub fn object_bytes(object: &str) -> Vec<u8> {
+    std::process::Command::new("git")
+        .args(["cat-file", "blob", object])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| o.stdout)
+        .unwrap_or_default()
+}
+
+pub fn config_value(key: &str) -> Option<String> {
+    run(vec!["config", "--get", key])
+        .first()
+        .map(|v| v.trim().to_owned())
+        .filter(|v| !v.is_empty())
+}
+
+pub fn config_values(key: &str) -> Vec<String> {
+    run(vec!["config", "--get-all", key])
+        .iter()
+        .map(|v| v.trim().to_owned())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+pub fn config_flag(key: &str) -> bool {
+    run(vec!["config", "--type=bool", "--get", key])
+        .first()
+        .is_some_and(|v| v.trim() == "true")
+}
+
+// Same as config_flag, for flags which are on unless turned off.
+pub fn config_flag_or(key: &str, default: bool) -> bool {
+    run(vec!["config", "--type=bool", "--get", key])
+        .first()
+        .map_or(default, |v| v.trim() == "true")
+}
+
+// Content of the file configured in 'commit.template', if any.
+pub fn commit_template() -> Vec<String> {
+    run(vec!["config", "--path", "--get", "commit.template"])
+        .first()
+        .map(|p| p.trim().to_owned())
+        .filter(|p| !p.is_empty())
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .map(|t| t.lines().map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SubmoduleState {
+    Uninitialized,
+    InSync,
+    // The checked out commit differs from the one recorded in the index.
+    CommitChanged,
+    Conflicted
+}
+
+pub struct Submodule {
+    pub path: String,
+    pub recorded: String,
+    pub checked_out: Option<String>,
+    pub state: SubmoduleState,
+    pub dirty: bool
+}
+
+pub fn submodules() -> Vec<Submodule> {
+    let mut submodules = vec![];
+
+    // Lines look like '+<hash> <path> (<describe>)', the first column
+    // tells whether the checked out commit matches the recorded one.
+    // Paths may contain spaces, uninitialized ones have no describe.
+    for line in run(vec!["submodule", "status"]) {
+        let mut chars = line.chars();
+        let Some(marker) = chars.next() else { continue };
+
+        let Some((hash, rest)) = chars.as_str().split_once(' ') else { continue };
+        if hash.len() < 7 { continue }
+
+        let path = match rest.strip_suffix(')').and_then(|r| r.rsplit_once(" (")) {
+            Some((path, _)) if marker != '-' => path,
+            _                                => rest
+        };
+
+        let state = match marker {
+            '-' => SubmoduleState::Uninitialized,
+            '+' => SubmoduleState::CommitChanged,
+            'U' => SubmoduleState::Conflicted,
+            _   => SubmoduleState::InSync
+        };
+
+        let recorded = run(vec!["ls-files", "-s", "--", path])
+            .first()
+            .and_then(|l| l.split_whitespace().nth(1).map(str::to_owned))
+            .unwrap_or_else(|| hash.to_owned());
+
+        let checked_out = match state {
+            SubmoduleState::Uninitialized => None,
+            _                             => Some(hash.to_owned())
+        };
+
+        let dirty = checked_out.is_some() && run(vec!["-C", path, "status", "--porcelain"])
+            .iter()
+            .any(|l| !l.is_empty());
+
+        submodules.push(Submodule { path: path.to_owned(), recorded, checked_out, state, dirty });
+    }
+
+    submodules
+}
+
+pub fn submodule_update(path: &str) -> Vec<String> {
+    run(vec!["submodule", "update", "--", path])
+}
+
+pub fn submodule_init(path: &str) -> Vec<String> {
+    run(vec!["submodule", "init", "--", path])
+}
+
+pub fn submodule_sync(path: &str) -> Vec<String> {
+    run(vec!["submodule", "sync", "--", path])
+}
+
+pub struct Worktree {
+    pub path: String,
+    pub head: String,
+    // None for detached and bare worktrees.
+    pub branch: Option<String>,
+    pub bare: bool,
+    pub locked: Option<String>,
+    pub prunable: Option<String>
+}
+
+pub fn worktrees() -> Vec<Worktree> {
+    let mut worktrees: Vec<Worktree> = vec![];
+
+    // Each worktree is a block of 'key value' lines, starting
+    // with its path and separated by empty lines.
+    for line in run(vec!["worktree", "list", "--porcelain"]) {
+        let (key, value) = line.split_once(' ').unwrap_or((&line, ""));
+
+        if key == "worktree" {
+            worktrees.push(Worktree {
+                path: value.to_owned(),
+                head: String::new(),
+                branch: None,
+                bare: false,
+                locked: None,
+                prunable: None
+            });
+            continue
+        }
+
+        let Some(worktree) = worktrees.last_mut() else { continue };
+
+        match key {
+            "HEAD"     => worktree.head = value.to_owned(),
+            "branch"   => worktree.branch = Some(value.trim_start_matches("refs/heads/").to_owned()),
+            "bare"     => worktree.bare = true,
+            "locked"   => worktree.locked = Some(value.to_owned()),
+            "prunable" => worktree.prunable = Some(value.to_owned()),
+            _          => { }
+        }
+    }
+
+    worktrees
+}
+
+pub fn toplevel() -> Option<String> {
+    run(vec!["rev-parse", "--show-toplevel"])
+        .first()
+        .filter(|l| !l.starts_with("fatal"))
+        .map(|l| l.trim().to_owned())
+}
+
+// Checks out an existing branch, or creates it when it does not exist yet.
+pub fn worktree_add(path: &str, branch: &str) -> Vec<String> {
+    if rev_parse(&format!("refs/heads/{}", branch)).is_some() {
+        run(vec!["worktree", "add", path, branch])
+    } else {
+        run(vec!["worktree", "add", "-b", branch, path])
+    }
+}
+
+pub fn worktree_remove(path: &str) -> Vec<String> {
+    run(vec!["worktree", "remove", path])
+}
+
+pub fn worktree_lock(path: &str) -> Vec<String> {
+    run(vec!["worktree", "lock", path])
+}
+
+pub fn worktree_unlock(path: &str) -> Vec<String> {
+    run(vec!["worktree", "unlock", path])
+}
+
+pub fn worktree_prune() -> Vec<String> {
+    run(vec!["worktree", "prune", "-v"])
+}
+
+pub fn is_bisecting() -> bool {
+    run(vec!["rev-parse", "--git-path", "BISECT_LOG"])
+        .first()
+        .is_some_and(|p| Path::new(p.trim()).exists())
+}
+
+pub struct BisectStatus {
+    // Commit to test next, the first bad commit once bisect is done.
+    pub candidate: String,
+    pub revisions_left: u32,
+    pub steps_left: u32,
+    // Number of commits which may still be the first bad one.
+    pub suspects: u32
+}
+
+// None until both a good and a bad commit are known.
+pub fn bisect_status() -> Option<BisectStatus> {
+    rev_parse("refs/bisect/bad")?;
+
+    let good: Vec<String> = run(vec!["for-each-ref", "--format=%(refname)", "refs/bisect/good-*"])
+        .into_iter()
+        .filter(|l| !l.is_empty())
+        .collect();
+    if good.is_empty() { return None }
+
+    let mut args = vec!["rev-list", "--bisect-vars", "refs/bisect/bad", "--not"];
+    args.extend(good.iter().map(String::as_str));
+
+    let vars = run(args);
+    let var = |name: &str| vars
+        .iter()
+        .find_map(|l| l.strip_prefix(&format!("{}=", name)))
+        .map(|v| v.trim_matches('\''));
+
+    Some(BisectStatus {
+        candidate: var("bisect_rev")?.to_owned(),
+        revisions_left: var("bisect_nr")?.parse().ok()?,
+        steps_left: var("bisect_steps")?.parse().ok()?,
+        suspects: var("bisect_all")?.parse().ok()?
+    })
+}
+
+// Marks a commit as good, bad or skipped, starting a bisect if
+// none is in progress.
+pub fn bisect(term: &str, commit_hash: &str) -> Vec<String> {
+    let mut output = vec![];
+
+    if !is_bisecting() {
+        output.extend(run(vec!["bisect", "start"]));
+    }
+
+    output.extend(run(vec!["bisect", term, commit_hash]));
+    output
+}
+
+pub fn bisect_reset() -> Vec<String> {
+    run(vec!["bisect", "reset"])
+}
+
+pub fn bisect_run(command: &str, on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    run_with_status(vec!["bisect", "run", "sh", "-c", command], on_update)
 }
 
-pub fn diff_file(path: &str) -> Vec<String> {
-    run(vec!["--no-pager", "diff", path])
+// Hash printed by bisect once it found the first bad commit.
+pub fn first_bad_commit(output: &[String]) -> Option<String> {
+    output
+        .iter()
+        .find_map(|l| l.strip_suffix(" is the first bad commit"))
+        .map(|h| h.trim().to_owned())
 }
 
-pub fn diff_commit(commit_hash: &str) -> Vec<String> {
-    run(vec!["--no-pager", "diff", &(commit_hash.to_owned() + "^!")])
+#[derive(Clone, PartialEq)]
+pub enum GrepTarget {
+    Worktree,
+    Index,
+    Revision(String)
+}
+
+impl GrepTarget {
+    pub fn description(&self) -> String {
+        match self {
+            GrepTarget::Worktree           => "worktree".to_owned(),
+            GrepTarget::Index              => "index".to_owned(),
+            GrepTarget::Revision(revision) => format!("revision {}", revision)
+        }
+    }
+}
+
+pub struct GrepMatch {
+    pub path: String,
+    pub line: usize,
+    pub text: String
+}
+
+// Searches tracked text files for a fixed string.
+pub fn grep(pattern: &str, target: &GrepTarget, ignore_case: bool) -> Vec<GrepMatch> {
+    let mut args = vec!["grep", "-n", "-z", "-I", "-F"];
+
+    if ignore_case {
+        args.push("-i");
+    }
+
+    args.extend(["-e", pattern]);
+
+    let prefix = match target {
+        GrepTarget::Worktree => String::new(),
+        GrepTarget::Index    => {
+            args.push("--cached");
+            String::new()
+        },
+        GrepTarget::Revision(revision) => {
+            args.push(revision);
+            format!("{}:", revision)
+        }
+    };
+
+    // With -z the lines look like 'path\0line\0text', paths of a
+    // revision are prefixed with 'revision:'.
+    run(args)
+        .iter()
+        .filter_map(|l| {
+            let mut columns = l.splitn(3, '\0');
+            let path = columns.next()?;
+            let line = columns.next()?.parse().ok()?;
+            let text = columns.next()?;
+
+            Some(GrepMatch {
+                path: path.strip_prefix(&prefix).unwrap_or(path).to_owned(),
+                line,
+                text: text.to_owned()
+            })
+        })
+        .collect()
+}
+
+// Content of a file as it is in the grep target.
+pub fn file_content(path: &str, target: &GrepTarget) -> Vec<String> {
+    match target {
+        GrepTarget::Worktree => std::fs::read(path)
+            .map(|b| String::from_utf8_lossy(&b).lines().map(str::to_owned).collect())
+            .unwrap_or_default(),
+        GrepTarget::Index              => run(vec!["show", &format!(":./{}", path)]),
+        GrepTarget::Revision(revision) => run(vec!["show", &format!("{}:./{}", revision, path)])
+    }
+}
+
+pub fn diff_commit(commit_hash: &str, options: &DiffOptions) -> Vec<String> {
+    run_diff(vec!["--no-pager", "diff"], options, vec![&(commit_hash.to_owned() + "^!")])
 }
 
 pub fn add_file(path: &str) {
@@ -147,8 +677,38 @@ pub fn unstage_file(path: &str) {
     run(vec!["reset", path]);
 }
 
-pub fn push(push_args: Option<Vec<&str>>) -> Vec<String> {
-    let mut args = vec!["push"];
+// Stages a patch cut out of a diff, reversed it unstages one taken
+// from the staged diff. The diff was made with the options, hunks
+// without context need apply to be told. Returns the error output,
+// empty when the patch applied.
+pub fn apply_to_index(patch: &str, reverse: bool, options: &DiffOptions) -> Vec<String> {
+    let mut args = vec!["apply", "--cached", "--recount"];
+
+    if reverse                    { args.push("--reverse"); }
+    if options.context_lines == 0 { args.push("--unidiff-zero"); }
+
+    let child = std::process::Command::new("git")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let Ok(mut child) = child else { return vec!["failed to execute git apply".to_owned()] };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(patch.as_bytes());
+    }
+
+    match child.wait_with_output() {
+        Ok(output) if output.status.success() => vec![],
+        Ok(output) => output_lines(output),
+        Err(error) => vec![error.to_string()]
+    }
+}
+
+pub fn push(push_args: Option<Vec<&str>>, on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    let mut args = vec!["push", "--progress"];
 
     if let Some(process_args) = push_args {
         args.extend(process_args);
@@ -159,24 +719,91 @@ pub fn push(push_args: Option<Vec<&str>>) -> Vec<String> {
     let current_branch = current_branch();
     args.extend(vec!["origin", &current_branch]);
 
-    run(args)
+    run_with_status(args, on_update)
+}
+
+pub fn fetch(on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    run_with_status(vec!["fetch", "--all", "--prune", "--progress"], on_update)
 }
 
-pub fn commit(commit_args: Option<Vec<&str>>) -> Vec<String> {
+pub fn pull(on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    run_with_status(vec!["pull", "--progress"], on_update)
+}
+
+// Rebases the current branch onto the given one. A rebase which stops
+// on a conflict is left for the user to continue or abort.
+pub fn rebase(onto: &str, on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    run_with_status(vec!["rebase", onto], on_update)
+}
+
+// Hooks run by git print to the same pipes, their output is passed to
+// `on_line` while they are still running.
+pub fn commit(commit_args: Option<Vec<&str>>, on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
     let mut args = vec!["commit"];
 
     if let Some(process_args) = commit_args {
         args.extend(process_args);
     }
 
-    let output = std::process::Command::new("git")
-        .args(args)
-        .spawn()
-        .unwrap()
-        .wait_with_output()
-        .expect("failed to execute process");
+    let previous_head = rev_parse("HEAD");
 
-    output_lines(output)
+    let output = run_with_status(args, on_update);
+
+    if let Some(previous_head) = previous_head {
+        if rev_parse("HEAD").as_ref() != Some(&previous_head) {
+            undo::record(Operation::Commit { previous_head });
+        }
+    }
+
+    output
+}
+
+// The message is passed in a file in the git directory, like the
+// COMMIT_EDITMSG file git itself uses.
+pub fn commit_with_message(message: &str, commit_args: Vec<&str>, on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    let Some(git_dir) = git_dir() else { return CommandOutput::error("Not a git repository.") };
+
+    let message_path = Path::new(&git_dir).join("GITKO_EDITMSG");
+    if let Err(error) = std::fs::write(&message_path, message) {
+        return CommandOutput::error(&format!("Could not write commit message: {}", error))
+    }
+
+    let message_path = message_path.to_string_lossy().to_string();
+    // The editor removed the template's comments already, 'strip' would
+    // also drop typed lines starting with '#'.
+    let mut args = vec!["-F", message_path.as_str(), "--cleanup=whitespace"];
+    args.extend(commit_args);
+
+    commit(Some(args), on_update)
+}
+
+pub fn git_dir() -> Option<String> {
+    run(vec!["rev-parse", "--absolute-git-dir"])
+        .first()
+        .filter(|l| !l.is_empty() && !l.starts_with("fatal"))
+        .map(|l| l.trim().to_owned())
+}
+
+pub fn last_commit_message() -> Vec<String> {
+    let mut message = run(vec!["log", "-1", "--format=%B"]);
+
+    while message.last().is_some_and(|l| l.is_empty()) {
+        message.pop();
+    }
+
+    message
+}
+
+// Files which end up in the next commit, including the changes of the
+// amended commit when amending.
+pub fn staged_summary(amend: bool) -> Vec<String> {
+    let mut args = vec!["--no-pager", "diff", "--cached", "--stat"];
+
+    if amend && rev_parse("HEAD^").is_some() {
+        args.push("HEAD^");
+    }
+
+    run(args)
 }
 
 pub fn branch() -> Vec<String> {
@@ -184,7 +811,14 @@ pub fn branch() -> Vec<String> {
 }
 
 pub fn checkout_branch(branch_name: &str) -> Vec<String> {
-    run(vec!["checkout", branch_name])
+    let previous_branch = current_branch();
+    let output = run(vec!["checkout", branch_name]);
+
+    if current_branch() != previous_branch {
+        undo::record(Operation::Checkout { previous_branch });
+    }
+
+    output
 }
 
 pub fn checkout_file(file_path: &str) -> Vec<String> {
@@ -192,21 +826,113 @@ pub fn checkout_file(file_path: &str) -> Vec<String> {
 }
 
 pub fn delete_branch(branch_name: &str) -> Vec<String> {
-    run(vec!["branch", "-D", branch_name])
+    let hash = rev_parse(&format!("refs/heads/{}", branch_name));
+    let output = run(vec!["branch", "-D", branch_name]);
+
+    if let Some(hash) = hash {
+        if rev_parse(&format!("refs/heads/{}", branch_name)).is_none() {
+            undo::record(Operation::DeleteBranch { name: branch_name.to_owned(), hash });
+        }
+    }
+
+    output
 }
 
 // This func should actually be called branch,
 // as in, the verb.
 pub fn create_branch(branch_name: &str) -> Vec<String> {
-    run(vec!["branch", branch_name])
+    let existed = rev_parse(&format!("refs/heads/{}", branch_name)).is_some();
+    let output = run(vec!["branch", branch_name]);
+
+    if !existed && rev_parse(&format!("refs/heads/{}", branch_name)).is_some() {
+        undo::record(Operation::CreateBranch { name: branch_name.to_owned() });
+    }
+
+    output
 }
 
 pub fn reset(commit_hash: &str, mode: &str) -> Vec<String> {
-    run(vec!["reset", mode, commit_hash])
+    let previous_head = rev_parse("HEAD");
+    let output = run(vec!["reset", mode, commit_hash]);
+
+    if let Some(previous_head) = previous_head {
+        if rev_parse("HEAD").as_ref() != Some(&previous_head) {
+            undo::record(Operation::Reset { previous_head, mode: mode.to_owned() });
+        }
+    }
+
+    output
 }
 
-pub fn show(commit_hash: &str) -> Vec<String> {
-    run(vec!["--no-pager", "show", commit_hash])
+// Moves a branch which is not checked out, `reset` is used for HEAD.
+pub fn force_branch(branch_name: &str, commit_hash: &str) -> Vec<String> {
+    let previous = rev_parse(&format!("refs/heads/{}", branch_name));
+    let output = run(vec!["branch", "--force", branch_name, commit_hash]);
+
+    if let Some(previous) = previous {
+        if rev_parse(&format!("refs/heads/{}", branch_name)).as_ref() != Some(&previous) {
+            undo::record(Operation::MoveBranch { name: branch_name.to_owned(), previous });
+        }
+    }
+
+    output
+}
+
+pub fn branch_names() -> Vec<String> {
+    run(vec!["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+        .into_iter()
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+pub fn reflog(reference: &str) -> Vec<String> {
+    run(vec!["--no-pager", "reflog", "show", "--format=%h %gd %gs", reference])
+}
+
+pub fn show(commit_hash: &str, options: &DiffOptions) -> Vec<String> {
+    run_diff(vec!["--no-pager", "show"], options, vec![commit_hash])
+}
+
+pub fn blame(path: &str, revision: Option<&str>) -> Vec<String> {
+    let mut args = vec!["--no-pager", "blame", "--porcelain"];
+
+    if let Some(rev) = revision {
+        args.push(rev);
+    }
+
+    args.extend(["--", path]);
+
+    run(args)
+}
+
+// The context option is left out, '-U' turns the patch on next to the
+// stats.
+pub fn show_numstat(commit_hash: &str, options: &DiffOptions) -> Vec<String> {
+    let option_args: Vec<String> = options.args().into_iter().filter(|a| !a.starts_with("-U")).collect();
+
+    let mut args = vec!["--no-pager", "show", "--numstat", "--format="];
+    args.extend(option_args.iter().map(String::as_str));
+    args.push(commit_hash);
+
+    run(args)
+}
+
+pub fn log_range(range: &str) -> Vec<String> {
+    run(vec!["--no-pager", "log", "--oneline", "--no-decorate", range])
+}
+
+pub fn diff_range_name_status(range: &str, options: &DiffOptions) -> Vec<String> {
+    run_diff(vec!["--no-pager", "diff", "--name-status"], options, vec![range])
+}
+
+// Renames and copies need both paths, the new one alone is diffed as
+// an added file.
+pub fn diff_range_file(range: &str, from: Option<&str>, path: &str, options: &DiffOptions) -> Vec<String> {
+    let mut rest = vec![range, "--"];
+    rest.extend(from);
+    rest.push(path);
+
+    run_diff(vec!["--no-pager", "diff"], options, rest)
 }
 
 pub fn log(max_count: Option<u32>) -> Vec<String> {
@@ -239,9 +965,25 @@ pub fn run(args: Vec<&str>) -> Vec<String> {
     output_lines(output)
 }
 
+// Passes the output to `on_update` as soon as git prints it and keeps
+// which stream a line came from, the progress updates and the exit
+// code. Returning false from `on_update` interrupts git. Git never
+// gets the terminal, an editor or a credential prompt started by it
+// would fight with the TUI over it.
+pub fn run_with_status(args: Vec<&str>, on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    let mut command = std::process::Command::new("git");
+    command
+        .args(args)
+        .env("GIT_EDITOR", "true")
+        .env("GIT_TERMINAL_PROMPT", "0");
+
+    process::run(command, on_update)
+}
+
 fn output_lines(output: std::process::Output) -> Vec<String> {
     let descriptor = if output.stdout.is_empty() { output.stderr } else { output.stdout };
-    let output_str = String::from_utf8(descriptor).expect("invalid string encoding");
+    // Diffs of files in other encodings are shown lossy instead of panicking.
+    let output_str = String::from_utf8_lossy(&descriptor);
 
     if output_str.is_empty() {
         vec![]
@@ -249,3 +991,31 @@ fn output_lines(output: std::process::Output) -> Vec<String> {
         output_str.split('\n').map(str::to_owned).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::first_bad_commit;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn first_bad_commit_is_found_in_bisect_output() {
+        let output = lines(
+            "running  'sh' '-c' 'make test'\n\
+             3c1f6a2d9e8b7a6c5d4e3f2a1b0c9d8e7f6a5b4c is the first bad commit\n\
+             commit 3c1f6a2d9e8b7a6c5d4e3f2a1b0c9d8e7f6a5b4c\n\
+             bisect found first bad commit"
+        );
+
+        assert_eq!(first_bad_commit(&output).as_deref(), Some("3c1f6a2d9e8b7a6c5d4e3f2a1b0c9d8e7f6a5b4c"));
+    }
+
+    #[test]
+    fn first_bad_commit_is_none_while_bisecting() {
+        let output = lines("Bisecting: 3 revisions left to test after this (roughly 2 steps)");
+
+        assert_eq!(first_bad_commit(&output), None);
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use gitko_render::{Line, Part, Style};
+
+// Same heuristic as git, a NUL byte early in the file means binary.
+const BINARY_CHECK_LENGTH: usize = 8000;
+const HEXDUMP_WIDTH: usize = 16;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .take(BINARY_CHECK_LENGTH)
+        .any(|b| *b == 0)
+}
+
+pub fn guess_mime(path: &str, bytes: &[u8]) -> &'static str {
+    const SIGNATURES: [(&[u8], &str); 10] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x7fELF", "application/x-elf"),
+        (b"\x00asm", "application/wasm"),
+        (b"BM", "image/bmp")
+    ];
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(s, _)| bytes.starts_with(s)) {
+        return mime
+    }
+
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match extension.as_str() {
+        "png"                => "image/png",
+        "jpg" | "jpeg"       => "image/jpeg",
+        "gif"                => "image/gif",
+        "svg"                => "image/svg+xml",
+        "webp"               => "image/webp",
+        "ico"                => "image/x-icon",
+        "pdf"                => "application/pdf",
+        "zip" | "jar"        => "application/zip",
+        "gz" | "tgz"         => "application/gzip",
+        "wasm"               => "application/wasm",
+        "ttf" | "otf"        => "font/ttf",
+        "woff" | "woff2"     => "font/woff",
+        "mp3"                => "audio/mpeg",
+        "mp4"                => "video/mp4",
+        "so" | "o" | "a"     => "application/x-object",
+        _ if is_binary(bytes) => "application/octet-stream",
+        _                    => "text/plain"
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Sizes are None when the file does not exist on that side,
+// e.g. the old size of an added file.
+pub fn binary_summary(path: &str, mime: &str, old_size: Option<u64>, new_size: Option<u64>) -> Vec<Line> {
+    let size = |s: Option<u64>| s.map(format_size).unwrap_or_else(|| "none".to_owned());
+
+    let size_line = match (old_size, new_size) {
+        (None, Some(new)) => format_size(new),
+        _                 => format!("{} -> {}", size(old_size), size(new_size))
+    };
+
+    let field = |name: &str, value: &str| Line::new(vec![
+        Part::new(name, Some(vec![Style::Bold])),
+        Part::plain(value)
+    ]);
+
+    vec![
+        Line::from_str("Binary file", Some(vec![Style::Bold, Style::Underlined])),
+        field("Path: ", path),
+        field("Type: ", mime),
+        field("Size: ", &size_line),
+        Line::empty(),
+        Line::plain("x: toggle hexdump")
+    ]
+}
+
+pub fn hexdump(bytes: &[u8], max_lines: usize) -> Vec<String> {
+    bytes
+        .chunks(HEXDUMP_WIDTH)
+        .take(max_lines)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+                .collect();
+
+            format!(
+                "{:08x}  {:<width$}  |{}|",
+                i * HEXDUMP_WIDTH,
+                hex.join(" "),
+                ascii,
+                width = HEXDUMP_WIDTH * 3 - 1
+            )
+        })
+        .collect()
+}
+
+pub fn hexdump_line_count(bytes: &[u8]) -> usize {
+    bytes.len().div_ceil(HEXDUMP_WIDTH)
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::git;
+use crate::screen;
+use crate::editor::open_in_editor;
+use crate::gitko::detailed_commit_window::DetailedCommitWindow;
+use crate::searchable::{SearchableComponent, register_search_handlers};
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, TaskResult, Window, RGB};
+
+use gitko_common::ascii_table::{KEY_ETB, KEY_E_LOWER, KEY_LF, KEY_P_LOWER};
+
+// Lines above the first blamed line.
+const HEADER_LINES: usize = 2;
+const HASH_LENGTH: usize = 8;
+const AUTHOR_LENGTH: usize = 14;
+const UNCOMMITTED_HASH: &str = "0000000000000000000000000000000000000000";
+
+struct BlameCommit {
+    author: String,
+    author_time: u64,
+    previous: Option<(String, String)>
+}
+
+struct BlameLine {
+    hash: String,
+    line_number: usize,
+    content: String
+}
+
+pub struct BlameWindow {
+    path: String,
+    revision: Option<String>,
+    // Revisions and paths blamed before the current one, so we can
+    // walk back after blaming at a parent commit.
+    history: Vec<(Option<String>, String)>,
+    commits: HashMap<String, BlameCommit>,
+    // Counts the loads, results of an older one which arrive after a
+    // newer one started are dropped.
+    load: usize,
+    term: String
+}
+
+// Blaming a file with a long history takes a while, it runs on
+// another thread.
+struct Blame {
+    output: Vec<String>,
+    commits: HashMap<String, BlameCommit>,
+    lines: Vec<BlameLine>
+}
+
+impl BlameWindow {
+    pub fn new(path: &str, revision: Option<&str>) -> BlameWindow {
+        BlameWindow {
+            path: path.to_owned(),
+            revision: revision.map(str::to_owned),
+            history: vec![],
+            commits: HashMap::new(),
+            load: 0,
+            term: "".to_owned()
+        }
+    }
+
+    fn cursor_commit_hash(&self, window: &Window) -> Option<String> {
+        let line = window.get_cursor_line();
+        let short_hash = line.get(..HASH_LENGTH)?;
+
+        self.commits
+            .keys()
+            .find(|h| h.starts_with(short_hash) && h.as_str() != UNCOMMITTED_HASH)
+            .cloned()
+    }
+
+    fn open_commit(&mut self, window: &mut Window) -> bool {
+        if let Some(hash) = self.cursor_commit_hash(window) {
+            Renderer::new(
+                &mut DetailedCommitWindow::new(&hash),
+                ScreenSize::max(),
+                Position::default(),
+                screen()
+            ).render();
+        }
+
+        true
+    }
+
+    fn blame_parent(&mut self, window: &mut Window) -> bool {
+        let Some(hash) = self.cursor_commit_hash(window) else { return true };
+        let Some(commit) = self.commits.get(&hash) else { return true };
+
+        // The root commit has nothing before it to blame.
+        if let Some((parent, path)) = commit.previous.clone() {
+            self.history.push((self.revision.take(), self.path.clone()));
+
+            self.revision = Some(parent);
+            self.path = path;
+
+            self.on_start(window);
+        }
+
+        true
+    }
+
+    // Only the blame of the worktree version matches the file on disk.
+    fn open_editor(&mut self, window: &mut Window) -> bool {
+        if self.revision.is_some() { return true }
+
+        let line = window.cursor_line_index()
+            .checked_sub(HEADER_LINES)
+            .map(|l| l + 1);
+
+        open_in_editor(&self.path, line);
+
+        self.on_start(window);
+        true
+    }
+
+    // Esc stops a search first, then walks back.
+    fn blame_back(&mut self, window: &mut Window) -> bool {
+        if !self.term.is_empty() {
+            return self.search_stop(window)
+        }
+
+        match self.history.pop() {
+            Some((revision, path)) => {
+                self.revision = revision;
+                self.path = path;
+
+                self.on_start(window);
+                true
+            }
+            None => false
+        }
+    }
+}
+
+fn parse_porcelain(output: &[String]) -> (HashMap<String, BlameCommit>, Vec<BlameLine>) {
+    let mut commits: HashMap<String, BlameCommit> = HashMap::new();
+    let mut lines: Vec<BlameLine> = vec![];
+
+    let mut current: Option<(String, usize)> = None;
+
+    for line in output {
+        if let Some(content) = line.strip_prefix('\t') {
+            if let Some((hash, line_number)) = current.take() {
+                lines.push(BlameLine { hash, line_number, content: content.to_owned() });
+            }
+            continue
+        }
+
+        match &current {
+            None => {
+                // Header line: <hash> <original line> <final line> [<group size>]
+                let header: Vec<&str> = line.split(' ').collect();
+                if header.len() < 3 || header[0].len() != 40 { continue }
+
+                let hash = header[0].to_owned();
+                let line_number = header[2].parse().unwrap_or(0);
+
+                commits.entry(hash.clone()).or_insert(BlameCommit {
+                    author: "".to_owned(),
+                    author_time: 0,
+                    previous: None
+                });
+
+                current = Some((hash, line_number));
+            }
+            Some((hash, _)) => {
+                let Some(commit) = commits.get_mut(hash) else { continue };
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+                match key {
+                    "author"      => commit.author = value.to_owned(),
+                    "author-time" => commit.author_time = value.parse().unwrap_or(0),
+                    "previous"    => {
+                        if let Some((parent, path)) = value.split_once(' ') {
+                            commit.previous = Some((parent.to_owned(), path.to_owned()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    (commits, lines)
+}
+
+fn age_seconds(time: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    now.saturating_sub(time)
+}
+
+fn format_age(seconds: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64   = 60 * MINUTE;
+    const DAY: u64    = 24 * HOUR;
+    const MONTH: u64  = 30 * DAY;
+    const YEAR: u64   = 365 * DAY;
+
+    let (amount, unit) = match seconds {
+        s if s < HOUR  => (s / MINUTE, "minute"),
+        s if s < DAY   => (s / HOUR, "hour"),
+        s if s < MONTH => (s / DAY, "day"),
+        s if s < YEAR  => (s / MONTH, "month"),
+        s              => (s / YEAR, "year")
+    };
+
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{} {}{} ago", amount, unit, plural)
+}
+
+// Newer changes are brighter, older ones fade out towards grey.
+fn age_color(seconds: u64) -> RGB {
+    const DAY: u64 = 24 * 60 * 60;
+
+    match seconds / DAY {
+        0          => (255, 215, 0),
+        1..=7      => (230, 170, 40),
+        8..=30     => (200, 130, 60),
+        31..=180   => (160, 110, 90),
+        181..=365  => (130, 110, 110),
+        _          => (100, 100, 100)
+    }
+}
+
+impl Component<BlameWindow> for BlameWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        self.load += 1;
+
+        let (load, path, revision) = (self.load, self.path.clone(), self.revision.clone());
+
+        window.spawn_task(&format!("Blaming {}", self.path), move || {
+            let output = git::blame(&path, revision.as_deref());
+            let (commits, lines) = parse_porcelain(&output);
+
+            (load, Blame { output, commits, lines })
+        });
+    }
+
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        let Ok(loaded) = result.downcast::<(usize, Blame)>() else { return };
+        let (load, Blame { output, commits, lines: blame_lines }) = *loaded;
+        if load != self.load { return }
+
+        let revision = self.revision.clone().unwrap_or_else(|| "HEAD".to_owned());
+        let mut lines: Vec<Line> = vec![
+            Line::new(vec![
+                Part::new("Blame:", Some(vec![Style::Bold, Style::Underlined])),
+                Part::plain(&format!(" {} @ {}", self.path, revision))
+            ]),
+            Line::empty()
+        ];
+
+        if blame_lines.is_empty() {
+            lines.extend(output.iter().map(|l| Line::plain(l)));
+        }
+
+        for blame_line in &blame_lines {
+            let Some(commit) = commits.get(&blame_line.hash) else { continue };
+
+            let (author, age, color) = if blame_line.hash == UNCOMMITTED_HASH {
+                ("Not committed".to_owned(), "".to_owned(), (255, 255, 255))
+            } else {
+                let seconds = age_seconds(commit.author_time);
+                (commit.author.clone(), format_age(seconds), age_color(seconds))
+            };
+
+            let gutter = format!(
+                "{hash} {author:<author_width$.author_width$} {age:>14} {number:>5}",
+                hash = &blame_line.hash[..HASH_LENGTH],
+                author = author,
+                author_width = AUTHOR_LENGTH,
+                age = age,
+                number = blame_line.line_number
+            );
+
+            lines.push(Line::new(vec![
+                Part::painted(&gutter, color, (0, 0, 0)),
+                Part::plain(" | "),
+                Part::plain(&blame_line.content)
+            ]));
+        }
+
+        self.commits = commits;
+        window.set_lines(lines);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<BlameWindow>) {
+        handlers.insert(KEY_LF, BlameWindow::open_commit);
+        handlers.insert(KEY_P_LOWER, BlameWindow::blame_parent);
+        handlers.insert(KEY_E_LOWER, BlameWindow::open_editor);
+
+        // Replaces the Esc handler of the search.
+        register_search_handlers(handlers);
+        handlers.insert(KEY_ETB, BlameWindow::blame_back);
+    }
+}
+
+impl SearchableComponent<BlameWindow> for BlameWindow {
+    fn term(&self) -> String {
+        self.term.clone()
+    }
+
+    fn set_term(&mut self, term: String) {
+        self.term = term;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_porcelain;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_owned).collect()
+    }
+
+    const FIRST: &str = "1111111111111111111111111111111111111111";
+    const SECOND: &str = "2222222222222222222222222222222222222222";
+
+    #[test]
+    fn parse_porcelain_reads_commits_once_and_every_line() {
+        let output = lines(&format!(
+            "{first} 1 1 2\nauthor Jane Doe\nauthor-time 1700000000\nfilename a.rs\n\tfn main() {{\n\
+             {first} 2 2\n\t}}\n\
+             {second} 5 3 1\nauthor John Doe\nauthor-time 1600000000\n\
+             previous {first} old name.rs\nfilename a.rs\n\t// end",
+            first = FIRST,
+            second = SECOND
+        ));
+
+        let (commits, blame_lines) = parse_porcelain(&output);
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[FIRST].author, "Jane Doe");
+        assert_eq!(commits[FIRST].author_time, 1700000000);
+        assert_eq!(commits[FIRST].previous, None);
+        assert_eq!(commits[SECOND].previous, Some((FIRST.to_owned(), "old name.rs".to_owned())));
+
+        let parsed: Vec<(&str, usize, &str)> = blame_lines
+            .iter()
+            .map(|l| (l.hash.as_str(), l.line_number, l.content.as_str()))
+            .collect();
+
+        assert_eq!(parsed, vec![(FIRST, 1, "fn main() {"), (FIRST, 2, "}"), (SECOND, 3, "// end")]);
+    }
+
+    #[test]
+    fn parse_porcelain_skips_what_is_not_blame_output() {
+        let (commits, blame_lines) = parse_porcelain(&lines("fatal: no such path 'a.rs' in HEAD"));
+
+        assert!(commits.is_empty());
+        assert!(blame_lines.is_empty());
+    }
+}
@@ -1,16 +1,50 @@
 use crate::git;
+use crate::undo::register_undo_handlers;
 use crate::{screen, max_width};
-use gitko_render::{Component, KeyHandlers, Line, Renderer, ScreenSize, Window, Position};
+use gitko_render::{Component, KeyHandlers, Line, Renderer, ScreenSize, Window, Position, Part};
 
-use gitko_common::ascii_table::{KEY_D_LOWER, KEY_LF, KEY_N_LOWER};
+use gitko_common::ascii_table::{KEY_D_LOWER, KEY_LF, KEY_M_LOWER, KEY_N_LOWER, KEY_R_LOWER};
 
 use crate::gitko::text_window::TextWindow;
 use crate::gitko::input_window::InputWindow;
 use crate::gitko::prompt_window::PromptWindow;
+use crate::gitko::compare_window::CompareWindow;
+use crate::gitko::command_output_window::run_with_output;
 
-pub struct BranchWindow { }
+pub struct BranchWindow {
+    marked_branch: Option<String>
+}
 
 impl BranchWindow {
+    pub fn new() -> BranchWindow {
+        BranchWindow { marked_branch: None }
+    }
+
+    // Marks the first side of a comparison, marking a second branch
+    // opens the comparison between the two.
+    fn mark_branch(&mut self, window: &mut Window) -> bool {
+        let line = window.get_cursor_line();
+        let branch = line.trim_start_matches('*').trim().to_owned();
+        if branch.is_empty() { return true }
+
+        match self.marked_branch.take() {
+            Some(marked) if marked != branch => {
+                Renderer::new(
+                    &mut CompareWindow::new(&marked, &branch),
+                    ScreenSize::max(),
+                    Position::default(),
+                    screen()
+                ).render();
+            }
+            Some(_) => { }
+            None    => { self.marked_branch = Some(branch); }
+        }
+
+        self.on_start(window);
+
+        true
+    }
+
     fn open_delete_branch_prompt(&mut self, window: &mut Window) -> bool {
         let line = window.get_cursor_line();
         if line.trim().is_empty() { return true }
@@ -47,6 +81,19 @@ impl BranchWindow {
         true
     }
 
+    // Rebases the current branch onto the one under the cursor.
+    fn rebase_onto_branch(&mut self, window: &mut Window) -> bool {
+        let line = window.get_cursor_line();
+        if line.trim().is_empty() || line.starts_with('*') { return true }
+
+        let branch = line.trim().to_owned();
+        run_with_output(&format!("git rebase {}", branch), false, |on_update| git::rebase(&branch, on_update));
+
+        self.on_start(window);
+
+        true
+    }
+
     fn create_branch(&mut self, window: &mut Window) -> bool {
         Renderer::new(
             &mut TextWindow {
@@ -75,10 +122,18 @@ impl BranchWindow {
 
 impl Component<BranchWindow> for BranchWindow {
     fn on_start(&mut self, window: &mut Window) {
+        let marked = self.marked_branch.clone().unwrap_or_default();
+
         window.set_lines(
             git::branch()
                 .iter()
-                .map(|l| Line::plain(l))
+                .map(|l| {
+                    if !marked.is_empty() && l.trim_start_matches('*').trim() == marked {
+                        Line::new(vec![Part::painted(l, (255, 255, 0), (0, 0, 0))])
+                    } else {
+                        Line::plain(l)
+                    }
+                })
                 .collect()
         );
     }
@@ -86,6 +141,10 @@ impl Component<BranchWindow> for BranchWindow {
     fn register_handlers(&self, handlers: &mut KeyHandlers<BranchWindow>) {
         handlers.insert(KEY_D_LOWER, BranchWindow::open_delete_branch_prompt);
         handlers.insert(KEY_LF, BranchWindow::checkout_branch);
+        handlers.insert(KEY_M_LOWER, BranchWindow::mark_branch);
         handlers.insert(KEY_N_LOWER, BranchWindow::create_branch);
+        handlers.insert(KEY_R_LOWER, BranchWindow::rebase_onto_branch);
+
+        register_undo_handlers(handlers);
     }
 }
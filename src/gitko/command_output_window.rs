@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use crate::{screen, max_height, max_width};
+use crate::process::{CommandOutput, OutputLine, Update};
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, Window, RGB};
+
+use gitko_common::ascii_table::{KEY_ETB, KEY_LF, KEY_R_LOWER};
+
+const STDERR_COLOR: RGB = (255, 128, 0);
+const SUCCESS_COLOR: RGB = (0, 255, 0);
+const FAILURE_COLOR: RGB = (255, 0, 0);
+const PROGRESS_COLOR: RGB = (0, 255, 255);
+
+const MIN_PANEL_HEIGHT: i32 = 8;
+
+// Output of a git command, shown in a panel at the bottom while it is
+// running and followed by whether it succeeded.
+pub struct CommandOutputWindow {
+    title: String,
+    lines: Vec<OutputLine>,
+    // The latest progress update, e.g. 'Receiving objects:  42%'.
+    progress: Option<OutputLine>,
+    // None while the command is still running.
+    status: Option<Option<i32>>,
+    cancelling: bool,
+    interrupted: bool,
+    // Failed commands can be run again without the hooks.
+    retryable: bool,
+    retry_requested: bool
+}
+
+impl CommandOutputWindow {
+    fn close(&mut self, _window: &mut Window) -> bool {
+        false
+    }
+
+    fn retry(&mut self, _window: &mut Window) -> bool {
+        if !self.can_retry() { return true }
+
+        self.retry_requested = true;
+        false
+    }
+
+    fn can_retry(&self) -> bool {
+        self.retryable && !self.interrupted && matches!(self.status, Some(status) if status != Some(0))
+    }
+
+    fn status_line(&self) -> Line {
+        match self.status {
+            None if self.cancelling => Line::plain(&format!("Cancelling {}...", self.title)),
+            None => Line::plain(&format!("Running {}...  (esc: cancel)", self.title)),
+            Some(_) if self.interrupted => Line::new(vec![
+                Part::painted(&format!("{} cancelled", self.title), FAILURE_COLOR, (0, 0, 0))
+            ]),
+            Some(Some(0)) => Line::new(vec![
+                Part::painted(&format!("{} succeeded", self.title), SUCCESS_COLOR, (0, 0, 0))
+            ]),
+            Some(status) => {
+                let reason = match status {
+                    Some(code) => format!("exit code {}", code),
+                    None       => "no exit code".to_owned()
+                };
+
+                let mut parts = vec![
+                    Part::painted(&format!("{} failed ({})", self.title, reason), FAILURE_COLOR, (0, 0, 0))
+                ];
+
+                if self.can_retry() {
+                    parts.push(Part::plain("  r: retry with --no-verify"));
+                }
+
+                Line::new(parts)
+            }
+        }
+    }
+}
+
+impl Component<CommandOutputWindow> for CommandOutputWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        window.show_cursor(false);
+
+        let mut lines = vec![
+            Line::from_str(&format!("Output of {}:", self.title), Some(vec![Style::Bold, Style::Underlined]))
+        ];
+
+        lines.extend(self.lines.iter().map(|l| {
+            if l.stderr {
+                Line::new(vec![Part::painted(&l.text, STDERR_COLOR, (0, 0, 0))])
+            } else {
+                Line::plain(&l.text)
+            }
+        }));
+
+        if let Some(progress) = &self.progress {
+            lines.push(Line::new(vec![Part::painted(&progress.text, PROGRESS_COLOR, (0, 0, 0))]));
+        }
+
+        lines.push(Line::empty());
+        lines.push(self.status_line());
+
+        window.set_lines(lines);
+    }
+
+    fn on_exit(&mut self, window: &mut Window) {
+        window.show_cursor(true);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<CommandOutputWindow>) {
+        handlers.insert(KEY_LF, CommandOutputWindow::close);
+        handlers.insert(KEY_ETB, CommandOutputWindow::close);
+        handlers.insert(KEY_R_LOWER, CommandOutputWindow::retry);
+    }
+}
+
+// Runs the command while showing its output, esc interrupts it. Returns
+// the output and whether it should be run again with '--no-verify'.
+pub fn run_with_output(
+    title: &str,
+    retryable: bool,
+    run: impl FnOnce(&mut dyn FnMut(&Update) -> bool) -> CommandOutput
+) -> (CommandOutput, bool) {
+    let mut output_window = CommandOutputWindow {
+        title: title.to_owned(),
+        lines: vec![],
+        progress: None,
+        status: None,
+        cancelling: false,
+        interrupted: false,
+        retryable,
+        retry_requested: false
+    };
+
+    let height = (max_height() / 3).max(MIN_PANEL_HEIGHT).min(max_height());
+
+    let output = {
+        let mut renderer = Renderer::new(
+            &mut output_window,
+            ScreenSize { lines: height, cols: max_width() },
+            Position { x: 0, y: max_height() - height },
+            screen()
+        );
+
+        renderer.draw_to_end();
+
+        let output = run(&mut |update| {
+            match update {
+                Update::Line(line) => {
+                    renderer.component().lines.push(line.clone());
+                    renderer.component().progress = None;
+                    renderer.draw_to_end();
+                }
+                Update::Progress(line) => {
+                    renderer.component().progress = Some(line.clone());
+                    renderer.draw_to_end();
+                }
+                Update::Waiting => {}
+            }
+
+            if renderer.component().cancelling { return false }
+
+            // Other keys are dropped, the command has no input.
+            if renderer.poll_input(Duration::ZERO) == Some(KEY_ETB) {
+                renderer.component().cancelling = true;
+                renderer.draw_to_end();
+                return false
+            }
+
+            true
+        });
+
+        renderer.component().status = Some(output.status);
+        renderer.component().interrupted = output.interrupted;
+        renderer.component().progress = None;
+        renderer.draw_to_end();
+        renderer.render();
+
+        output
+    };
+
+    (output, output_window.retry_requested)
+}
@@ -1,12 +1,18 @@
 use crate::git;
-use crate::gitko::diff_display::color_diff_line;
+use crate::git::DiffOptions;
+use crate::diffable::{DiffableComponent, diff_options_header, register_diff_option_handlers};
+use crate::gitko::diff_display::{DiffCache, DiffLayout};
 use crate::searchable::{SearchableComponent, register_search_handlers};
 use gitko_render::{Component, KeyHandlers, Window};
 
-use gitko_common::ascii_table::{KEY_J_LOWER, KEY_K_LOWER};
+use gitko_common::ascii_table::{KEY_J_LOWER, KEY_K_LOWER, KEY_S_LOWER};
 
 pub struct CommitDiffWindow {
     commit_hash: String,
+    diff: Vec<String>,
+    layout: DiffLayout,
+    rendered: DiffCache,
+    options: DiffOptions,
     term: String
 }
 
@@ -14,6 +20,10 @@ impl CommitDiffWindow {
     pub fn new(commit_hash: &str) -> CommitDiffWindow {
         CommitDiffWindow {
             commit_hash: commit_hash.to_owned(),
+            diff: vec![],
+            layout: DiffLayout::Unified,
+            rendered: DiffCache::default(),
+            options: DiffOptions::default(),
             term: "".to_owned()
         }
     }
@@ -43,18 +53,27 @@ impl CommitDiffWindow {
 
         true
     }
+
+    fn toggle_layout(&mut self, window: &mut Window) -> bool {
+        self.layout = self.layout.toggle();
+        self.render(window);
+        true
+    }
+
+    fn render(&mut self, window: &mut Window) {
+        let mut lines = vec![diff_options_header(&self.options)];
+        lines.extend(self.rendered.render(&self.diff, None, self.layout, window.width()));
+
+        window.set_lines(lines);
+    }
 }
 
 impl Component<CommitDiffWindow> for CommitDiffWindow {
     fn on_start(&mut self, window: &mut Window) {
         window.show_cursor(false);
 
-        window.set_lines(
-            git::diff_commit(&self.commit_hash)
-                .iter()
-                .map(|l| color_diff_line(l))
-                .collect()
-        );
+        self.diff = git::diff_commit(&self.commit_hash, &self.options);
+        self.render(window);
     }
 
     fn on_exit(&mut self, window: &mut Window) {
@@ -67,8 +86,16 @@ impl Component<CommitDiffWindow> for CommitDiffWindow {
 
         handlers.insert(4, CommitDiffWindow::jump_screen_down);
         handlers.insert(21, CommitDiffWindow::jump_screen_up);
+        handlers.insert(KEY_S_LOWER, CommitDiffWindow::toggle_layout);
 
         register_search_handlers(handlers);
+        register_diff_option_handlers(handlers);
+    }
+}
+
+impl DiffableComponent<CommitDiffWindow> for CommitDiffWindow {
+    fn diff_options(&mut self) -> &mut DiffOptions {
+        &mut self.options
     }
 }
 
@@ -0,0 +1,611 @@
+use std::cell::Cell;
+
+use crate::git;
+use crate::message_history;
+use crate::screen;
+use crate::gitko::input_window::read_input;
+use crate::gitko::prompt_window::PromptWindow;
+use crate::gitko::command_output_window::run_with_output;
+use crate::gitko::commit_message::{add_trailer, conventional_commit_warning, is_comment, strip_comments, template_comments};
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, Window, RGB};
+
+use gitko_common::ascii_table::{KEY_ARROW_DOWN, KEY_ARROW_LEFT, KEY_ARROW_RIGHT, KEY_ARROW_UP, KEY_BACKSPACE, KEY_BS,
+                                KEY_DC2, KEY_DC4, KEY_DEL, KEY_DELETE, KEY_END, KEY_ETB, KEY_HOME, KEY_LF, KEY_NULL, KEY_SUB};
+
+// Lines above the message.
+const HEADER_LINES: usize = 2;
+const SUBJECT_LENGTH: usize = 50;
+const BODY_LENGTH: usize = 72;
+// Staged files are shown next to the message on terminals this wide.
+const MIN_SIDE_BY_SIDE_WIDTH: i32 = 110;
+const SUMMARY_SEPARATOR: &str = " | ";
+
+const FIXES_TRAILER: &str = "Fixes: ";
+
+const GUIDE_COLOR: RGB = (128, 128, 128);
+const WARNING_COLOR: RGB = (255, 255, 0);
+const ERROR_COLOR: RGB = (255, 0, 0);
+
+struct Snapshot {
+    message: Vec<String>,
+    row: usize,
+    column: usize
+}
+
+pub struct CommitEditorWindow {
+    // Extra arguments for 'git commit', like '--amend'.
+    args: Vec<String>,
+    message: Vec<String>,
+    row: usize,
+    // Counted in characters, not bytes.
+    column: usize,
+    undo_stack: Vec<Snapshot>,
+    // Consecutive typed characters are undone together.
+    typing: bool,
+    staged: Vec<String>,
+    // Comment lines of 'commit.template', removed before committing.
+    comments: Vec<String>,
+    // Enabled with 'git config gitko.conventionalCommits true'.
+    conventional: bool
+}
+
+impl CommitEditorWindow {
+    pub fn new(args: Vec<String>) -> CommitEditorWindow {
+        let amend = args.iter().any(|a| a == "--amend");
+
+        // A draft left by a commit that failed, e.g. rejected by a hook,
+        // takes precedence over the template.
+        let message = if amend {
+            git::last_commit_message()
+        } else {
+            message_history::load_draft().unwrap_or_else(git::commit_template)
+        };
+        let message = if message.is_empty() { vec![String::new()] } else { message };
+
+        CommitEditorWindow {
+            args,
+            message,
+            row: 0,
+            column: 0,
+            undo_stack: vec![],
+            typing: false,
+            staged: git::staged_summary(amend),
+            comments: template_comments(&git::commit_template()),
+            conventional: git::config_flag("gitko.conventionalCommits")
+        }
+    }
+
+    fn warning(&self) -> Option<String> {
+        if !self.conventional { return None }
+
+        let subject = self.message
+            .iter()
+            .find(|l| !self.comments.contains(l))
+            .map(String::as_str)
+            .unwrap_or("");
+
+        conventional_commit_warning(subject)
+    }
+
+    fn insert_trailer(&mut self, window: &mut Window) -> bool {
+        let mut trailers = vec![];
+
+        if let (Some(name), Some(email)) = (git::config_value("user.name"), git::config_value("user.email")) {
+            trailers.push(format!("Signed-off-by: {} <{}>", name, email));
+        }
+
+        // Configured with 'git config --add gitko.coAuthor "Name <email>"'.
+        trailers.extend(
+            git::config_values("gitko.coAuthor")
+                .iter()
+                .map(|a| format!("Co-authored-by: {}", a))
+        );
+
+        trailers.push(FIXES_TRAILER.to_owned());
+
+        let height = trailers.len() as i32 + 1;
+        let mut options = TrailerOptionsWindow { trailers, selected: None };
+
+        Renderer::new(
+            &mut options,
+            ScreenSize { lines: height, cols: window.width() },
+            Position { x: 0, y: window.height() - height },
+            screen()
+        ).render();
+
+        let trailer = match options.selected {
+            Some(trailer) if trailer == FIXES_TRAILER => {
+                let issue = read_input(window, "Enter the fixed issue, e.g. #123:");
+                if issue.is_empty() { None } else { Some(format!("{}{}", FIXES_TRAILER, issue)) }
+            }
+            selected => selected
+        };
+
+        if let Some(trailer) = trailer {
+            self.snapshot();
+            self.typing = false;
+
+            add_trailer(&mut self.message, &trailer);
+            self.row = self.row.min(self.message.len() - 1);
+            self.column = self.column.min(self.line_length());
+        }
+
+        self.render(window);
+        true
+    }
+
+    fn reuse_message(&mut self, window: &mut Window) -> bool {
+        let messages = message_history::history();
+        if messages.is_empty() {
+            self.render(window);
+            return true
+        }
+
+        let height = (messages.len() as i32 + 1).min(window.height());
+        let mut history = MessageHistoryWindow { messages, selected: None };
+
+        Renderer::new(
+            &mut history,
+            ScreenSize { lines: height, cols: window.width() },
+            Position { x: 0, y: window.height() - height },
+            screen()
+        ).render();
+
+        if let Some(message) = history.selected {
+            self.snapshot();
+            self.typing = false;
+
+            self.message = message;
+            self.row = 0;
+            self.column = 0;
+        }
+
+        self.render(window);
+        true
+    }
+
+    fn amend(&self) -> bool {
+        self.args.iter().any(|a| a == "--amend")
+    }
+
+    fn current_line(&self) -> &String {
+        &self.message[self.row]
+    }
+
+    fn line_length(&self) -> usize {
+        self.current_line().chars().count()
+    }
+
+    fn byte_index(&self, column: usize) -> usize {
+        self.current_line()
+            .char_indices()
+            .nth(column)
+            .map(|(i, _)| i)
+            .unwrap_or(self.current_line().len())
+    }
+
+    fn snapshot(&mut self) {
+        self.undo_stack.push(Snapshot {
+            message: self.message.clone(),
+            row: self.row,
+            column: self.column
+        });
+    }
+
+    fn undo(&mut self, window: &mut Window) -> bool {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            self.message = snapshot.message;
+            self.row = snapshot.row;
+            self.column = snapshot.column;
+        }
+
+        self.typing = false;
+        self.render(window);
+        true
+    }
+
+    fn insert_char(&mut self, c: char) {
+        if !self.typing || c.is_whitespace() {
+            self.snapshot();
+        }
+        self.typing = true;
+
+        let index = self.byte_index(self.column);
+        self.message[self.row].insert(index, c);
+        self.column += 1;
+    }
+
+    fn new_line(&mut self, window: &mut Window) -> bool {
+        self.snapshot();
+        self.typing = false;
+
+        let index = self.byte_index(self.column);
+        let rest = self.message[self.row].split_off(index);
+
+        self.row += 1;
+        self.column = 0;
+        self.message.insert(self.row, rest);
+
+        self.render(window);
+        true
+    }
+
+    fn delete_backward(&mut self, window: &mut Window) -> bool {
+        if self.column == 0 && self.row == 0 { return true }
+
+        self.snapshot();
+        self.typing = false;
+
+        if self.column == 0 {
+            // Joins the line with the previous one.
+            let line = self.message.remove(self.row);
+            self.row -= 1;
+            self.column = self.line_length();
+            self.message[self.row].push_str(&line);
+        } else {
+            self.column -= 1;
+            let index = self.byte_index(self.column);
+            self.message[self.row].remove(index);
+        }
+
+        self.render(window);
+        true
+    }
+
+    fn delete_forward(&mut self, window: &mut Window) -> bool {
+        let at_end = self.column >= self.line_length();
+        if at_end && self.row + 1 >= self.message.len() { return true }
+
+        self.snapshot();
+        self.typing = false;
+
+        if at_end {
+            let line = self.message.remove(self.row + 1);
+            self.message[self.row].push_str(&line);
+        } else {
+            let index = self.byte_index(self.column);
+            self.message[self.row].remove(index);
+        }
+
+        self.render(window);
+        true
+    }
+
+    fn move_left(&mut self, window: &mut Window) -> bool {
+        if self.column > 0 {
+            self.column -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.column = self.line_length();
+        }
+
+        self.moved(window)
+    }
+
+    fn move_right(&mut self, window: &mut Window) -> bool {
+        if self.column < self.line_length() {
+            self.column += 1;
+        } else if self.row + 1 < self.message.len() {
+            self.row += 1;
+            self.column = 0;
+        }
+
+        self.moved(window)
+    }
+
+    fn move_up(&mut self, window: &mut Window) -> bool {
+        if self.row > 0 {
+            self.row -= 1;
+            self.column = self.column.min(self.line_length());
+        }
+
+        self.moved(window)
+    }
+
+    fn move_down(&mut self, window: &mut Window) -> bool {
+        if self.row + 1 < self.message.len() {
+            self.row += 1;
+            self.column = self.column.min(self.line_length());
+        }
+
+        self.moved(window)
+    }
+
+    fn move_home(&mut self, window: &mut Window) -> bool {
+        self.column = 0;
+        self.moved(window)
+    }
+
+    fn move_end(&mut self, window: &mut Window) -> bool {
+        self.column = self.line_length();
+        self.moved(window)
+    }
+
+    fn moved(&mut self, window: &mut Window) -> bool {
+        self.typing = false;
+        self.render(window);
+        true
+    }
+
+    // Esc asks whether to commit, discard the message or keep editing.
+    // A message which is not a conventional commit is only committed
+    // or edited further, discarding it is always asked again.
+    fn finish(&mut self, window: &mut Window) -> bool {
+        let commit = Cell::new(false);
+        let discard = Cell::new(false);
+
+        let warning = self.warning();
+        let message = match &warning {
+            Some(warning) => format!("Not a conventional commit, {}. Commit anyway? y: commit, n/q: keep editing", warning),
+            None          => "Commit with this message? y: commit, n: discard, q: keep editing".to_owned()
+        };
+
+        Renderer::new(
+            &mut PromptWindow::new(
+                &message,
+                || commit.set(true),
+                || discard.set(warning.is_none())
+            ),
+            ScreenSize { lines: 1, cols: 0 },
+            Position { x: 0, y: window.height() - 1 },
+            screen()
+        ).render();
+
+        // Amends start from the last commit message, the draft belongs
+        // to the next new commit and is left alone by them.
+        let amend = self.amend();
+
+        if discard.get() && self.confirm_discard(window) {
+            if !amend { message_history::clear_draft(); }
+            return false
+        }
+
+        if !commit.get() {
+            self.render(window);
+            return true
+        }
+
+        // Kept until the commit succeeds, so a rejected message can be
+        // edited again instead of typed from scratch.
+        if !amend { message_history::save_draft(&self.message); }
+
+        let message = strip_comments(&self.message, &self.comments).join("\n") + "\n";
+        let mut args = self.args.clone();
+
+        let committed = loop {
+            let (output, retry) = run_with_output("git commit", !args.iter().any(|a| a == "--no-verify"), |on_line| {
+                git::commit_with_message(&message, args.iter().map(String::as_str).collect(), on_line)
+            });
+
+            if output.success() { break true }
+            if !retry { break false }
+
+            args.push("--no-verify".to_owned());
+        };
+
+        if committed {
+            if !amend { message_history::clear_draft(); }
+            message_history::record(&self.message);
+            return false
+        }
+
+        self.render(window);
+        true
+    }
+
+    fn confirm_discard(&self, window: &mut Window) -> bool {
+        let confirmed = Cell::new(false);
+
+        Renderer::new(
+            &mut PromptWindow::new(
+                "Are you sure you want to discard the message? y/n",
+                || confirmed.set(true),
+                || {}
+            ),
+            ScreenSize { lines: 1, cols: 0 },
+            Position { x: 0, y: window.height() - 1 },
+            screen()
+        ).render();
+
+        confirmed.get()
+    }
+
+    fn render(&mut self, window: &mut Window) {
+        let title = if self.amend() { "Amend commit message" } else { "Commit message" };
+
+        let mut title_parts = vec![
+            Part::new(title, Some(vec![Style::Bold, Style::Underlined])),
+            Part::plain("  (esc: commit or discard, ctrl+z: undo, ctrl+t: trailer, ctrl+r: previous messages)")
+        ];
+
+        if let Some(warning) = self.warning() {
+            title_parts.push(Part::painted(&format!("  {}", warning), ERROR_COLOR, (0, 0, 0)));
+        }
+
+        let mut lines = vec![
+            Line::new(title_parts),
+            Line::new(vec![Part::painted(&guide(), GUIDE_COLOR, (0, 0, 0))])
+        ];
+
+        let message_lines: Vec<Line> = self.message
+            .iter()
+            .enumerate()
+            .map(|(i, l)| message_line(i, l, self.comments.contains(l)))
+            .collect();
+
+        if window.width() >= MIN_SIDE_BY_SIDE_WIDTH {
+            let rows = message_lines.len().max(self.staged.len() + 1);
+
+            for i in 0..rows {
+                let mut parts = message_lines.get(i).map(|l| l.parts.clone()).unwrap_or_default();
+                let length: usize = parts.iter().map(|p| p.value.chars().count()).sum();
+
+                parts.push(Part::plain(&" ".repeat((BODY_LENGTH + 1).saturating_sub(length))));
+                parts.push(Part::painted(SUMMARY_SEPARATOR, GUIDE_COLOR, (0, 0, 0)));
+                parts.push(summary_part(i, &self.staged));
+
+                lines.push(Line::new(parts));
+            }
+        } else {
+            lines.extend(message_lines);
+            lines.push(Line::empty());
+            lines.extend((0..=self.staged.len()).map(|i| Line::new(vec![summary_part(i, &self.staged)])));
+        }
+
+        window.set_lines(lines);
+        window.move_to_line(HEADER_LINES + self.row);
+        window.move_to_column(self.column);
+    }
+}
+
+// Column ruler marking the subject and body line lengths.
+fn guide() -> String {
+    (1..=BODY_LENGTH)
+        .map(|c| if c == SUBJECT_LENGTH || c == BODY_LENGTH { '|' } else { '-' })
+        .collect()
+}
+
+fn summary_part(index: usize, staged: &[String]) -> Part {
+    match index {
+        0 => Part::new("Staged changes:", Some(vec![Style::Bold])),
+        _ => Part::plain(staged.get(index - 1).map(String::as_str).unwrap_or(""))
+    }
+}
+
+// The subject turns yellow after 50 and red after 72 characters, body
+// lines red after 72. The line between subject and body should be empty.
+fn message_line(index: usize, text: &str, comment: bool) -> Line {
+    if comment {
+        return Line::new(vec![Part::painted(text, GUIDE_COLOR, (0, 0, 0))])
+    }
+
+    if index == 1 && !text.is_empty() {
+        return Line::new(vec![Part::painted(text, ERROR_COLOR, (0, 0, 0))])
+    }
+
+    let warning_at = if index == 0 { SUBJECT_LENGTH } else { BODY_LENGTH };
+    let byte_at = |column: usize| text.char_indices().nth(column).map(|(i, _)| i).unwrap_or(text.len());
+
+    let (warning_start, error_start) = (byte_at(warning_at), byte_at(BODY_LENGTH));
+
+    let mut parts = vec![Part::plain(&text[..warning_start])];
+
+    if warning_start < error_start {
+        parts.push(Part::painted(&text[warning_start..error_start], WARNING_COLOR, (0, 0, 0)));
+    }
+
+    if error_start < text.len() {
+        parts.push(Part::painted(&text[error_start..], ERROR_COLOR, (0, 0, 0)));
+    }
+
+    Line::new(parts)
+}
+
+impl Component<CommitEditorWindow> for CommitEditorWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        window.show_cursor(true);
+        self.render(window);
+    }
+
+    // Every other key is text, including the ones which would
+    // otherwise move the cursor or close the window.
+    fn on_input(&mut self, window: &mut Window, key: i32) -> Option<bool> {
+        if key == KEY_NULL { return Some(true) }
+
+        let Some(c) = char::from_u32(key as u32) else { return Some(true) };
+        if c.is_control() { return Some(true) }
+
+        self.insert_char(c);
+        self.render(window);
+
+        Some(true)
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<CommitEditorWindow>) {
+        handlers.insert(KEY_LF, CommitEditorWindow::new_line);
+        handlers.insert(KEY_ETB, CommitEditorWindow::finish);
+        handlers.insert(KEY_SUB, CommitEditorWindow::undo);
+        handlers.insert(KEY_DC4, CommitEditorWindow::insert_trailer);
+        handlers.insert(KEY_DC2, CommitEditorWindow::reuse_message);
+
+        handlers.insert(KEY_BACKSPACE, CommitEditorWindow::delete_backward);
+        handlers.insert(KEY_DEL, CommitEditorWindow::delete_backward);
+        handlers.insert(KEY_BS, CommitEditorWindow::delete_backward);
+        handlers.insert(KEY_DELETE, CommitEditorWindow::delete_forward);
+
+        handlers.insert(KEY_ARROW_LEFT, CommitEditorWindow::move_left);
+        handlers.insert(KEY_ARROW_RIGHT, CommitEditorWindow::move_right);
+        handlers.insert(KEY_ARROW_UP, CommitEditorWindow::move_up);
+        handlers.insert(KEY_ARROW_DOWN, CommitEditorWindow::move_down);
+        handlers.insert(KEY_HOME, CommitEditorWindow::move_home);
+        handlers.insert(KEY_END, CommitEditorWindow::move_end);
+    }
+}
+
+struct TrailerOptionsWindow {
+    trailers: Vec<String>,
+    selected: Option<String>
+}
+
+impl TrailerOptionsWindow {
+    fn select(&mut self, window: &mut Window) -> bool {
+        let line = window.get_cursor_line();
+        self.selected = self.trailers.iter().find(|t| t.trim_end() == line.trim_end()).cloned();
+
+        false
+    }
+}
+
+impl Component<TrailerOptionsWindow> for TrailerOptionsWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        let mut lines = vec![Line::from_str("Add trailer:", Some(vec![Style::Bold, Style::Underlined]))];
+        lines.extend(self.trailers.iter().map(|t| Line::plain(t)));
+
+        window.set_lines(lines);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<TrailerOptionsWindow>) {
+        handlers.insert(KEY_LF, TrailerOptionsWindow::select);
+    }
+}
+
+struct MessageHistoryWindow {
+    messages: Vec<Vec<String>>,
+    selected: Option<Vec<String>>
+}
+
+impl MessageHistoryWindow {
+    fn select(&mut self, window: &mut Window) -> bool {
+        self.selected = window.cursor_line_index()
+            .checked_sub(1)
+            .and_then(|i| self.messages.get(i))
+            .cloned();
+
+        false
+    }
+}
+
+impl Component<MessageHistoryWindow> for MessageHistoryWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        let mut lines = vec![Line::from_str("Previous messages:", Some(vec![Style::Bold, Style::Underlined]))];
+
+        lines.extend(self.messages.iter().map(|m| {
+            let subject = m.iter().find(|l| !is_comment(l)).map(String::as_str).unwrap_or("");
+            let body_lines = m.iter().skip(1).filter(|l| !l.trim().is_empty() && !is_comment(l)).count();
+
+            match body_lines {
+                0 => Line::plain(subject),
+                n => Line::new(vec![
+                    Part::plain(subject),
+                    Part::painted(&format!("  (+{} lines)", n), GUIDE_COLOR, (0, 0, 0))
+                ])
+            }
+        }));
+
+        window.set_lines(lines);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<MessageHistoryWindow>) {
+        handlers.insert(KEY_LF, MessageHistoryWindow::select);
+    }
+}
@@ -0,0 +1,100 @@
+const CONVENTIONAL_TYPES: [&str; 11] = [
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert"
+];
+
+pub fn is_comment(line: &str) -> bool {
+    line.starts_with('#')
+}
+
+// Only the comments of the template are removed before committing,
+// typed lines like '#123 fix crash' are part of the message.
+pub fn template_comments(template: &[String]) -> Vec<String> {
+    template.iter().filter(|l| is_comment(l)).cloned().collect()
+}
+
+pub fn strip_comments(message: &[String], comments: &[String]) -> Vec<String> {
+    message.iter().filter(|l| !comments.contains(l)).cloned().collect()
+}
+
+// Lines like 'Signed-off-by: Name <email>', the token has no spaces.
+pub fn is_trailer(line: &str) -> bool {
+    line.split_once(": ")
+        .is_some_and(|(token, value)| !token.is_empty() && !token.contains(' ') && !value.trim().is_empty())
+}
+
+// Appends the trailer to the trailer block at the end of the message,
+// starting a new block after an empty line when there is none yet.
+pub fn add_trailer(message: &mut Vec<String>, trailer: &str) {
+    if message.iter().any(|l| l == trailer) { return }
+
+    let last = message
+        .iter()
+        .rposition(|l| !l.trim().is_empty() && !is_comment(l));
+
+    match last {
+        // Only the subject may be written later, keep its line and
+        // the empty line after it free.
+        None => {
+            while message.len() < 2 {
+                message.push(String::new());
+            }
+            message.insert(2, trailer.to_owned());
+        }
+        Some(last) if last > 0 && is_trailer(&message[last]) => {
+            message.insert(last + 1, trailer.to_owned());
+        }
+        Some(last) => {
+            message.insert(last + 1, trailer.to_owned());
+            message.insert(last + 1, String::new());
+        }
+    }
+}
+
+// Checks the subject against 'type(scope)!: description'.
+pub fn conventional_commit_warning(subject: &str) -> Option<String> {
+    let Some((prefix, description)) = subject.split_once(": ") else {
+        return Some("subject should look like 'type(scope): description'".to_owned())
+    };
+
+    let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, scope)) => (kind, Some(scope)),
+        None                => (prefix, None)
+    };
+
+    if let Some(scope) = scope {
+        if scope.len() < 2 || !scope.ends_with(')') {
+            return Some("scope should look like '(scope)'".to_owned())
+        }
+    }
+
+    if !CONVENTIONAL_TYPES.contains(&kind) {
+        return Some(format!("unknown type '{}', expected one of {}", kind, CONVENTIONAL_TYPES.join(", ")))
+    }
+
+    if description.trim().is_empty() {
+        return Some("description is empty".to_owned())
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_comments, template_comments};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn strip_comments_keeps_typed_lines_starting_with_a_hash() {
+        let template = lines("\n# Explain why the change was made.\n# Reference the issue.");
+        let message = lines("#123 fix crash\n\n# Explain why the change was made.\n#456 as well\n# Reference the issue.");
+
+        assert_eq!(
+            strip_comments(&message, &template_comments(&template)),
+            lines("#123 fix crash\n\n#456 as well")
+        );
+    }
+}
@@ -1,7 +1,5 @@
 use crate::screen;
-use crate::git;
-use crate::max_height;
-use crate::gitko::output_window::OutputWindow;
+use crate::gitko::commit_editor_window::CommitEditorWindow;
 use gitko_render::{Renderer, ScreenSize, Position, Component, KeyHandlers, Line, Window};
 
 use gitko_common::ascii_table::{KEY_LF};
@@ -10,23 +8,17 @@ pub struct CommitOptionsWindow { }
 
 impl CommitOptionsWindow {
     fn git_commit(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line().trim().to_owned();
+        let args = window.get_cursor_line()
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect();
 
-        let args = if line.is_empty() {
-            None
-        } else {
-            Some(vec![line.as_str()])
-        };
-
-        let output = git::commit(args);
-        let output_window_height = output.len() as i32 + 1;
-
-        if output.is_empty() { return false }
+        window.clear();
 
         Renderer::new(
-            &mut OutputWindow{ output },
-            ScreenSize { lines: output_window_height, cols: window.width() },
-            Position { x: 0, y: max_height() - output_window_height },
+            &mut CommitEditorWindow::new(args),
+            ScreenSize::max(),
+            Position::default(),
             screen()
         ).render();
 
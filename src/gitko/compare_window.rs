@@ -0,0 +1,206 @@
+use crate::git;
+use crate::git::DiffOptions;
+use crate::diffable::{DiffableComponent, diff_options_header, register_diff_option_handlers};
+use crate::screen;
+use crate::gitko::diff_display::{DiffCache, DiffLayout};
+use crate::gitko::detailed_commit_window::DetailedCommitWindow;
+use crate::searchable::{SearchableComponent, register_search_handlers};
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, Window};
+
+use gitko_common::ascii_table::{KEY_ETB, KEY_LF};
+
+struct ChangedFile {
+    status: String,
+    // The path on the other side of a rename or copy.
+    from: Option<String>,
+    path: String,
+    expanded: bool,
+    diff: Option<Vec<String>>,
+    rendered: DiffCache
+}
+
+impl ChangedFile {
+    fn header(&self) -> String {
+        match &self.from {
+            Some(from) => format!("{:<4} {} -> {}", self.status, from, self.path),
+            None       => format!("{:<4} {}", self.status, self.path)
+        }
+    }
+}
+
+pub struct CompareWindow {
+    from: String,
+    to: String,
+    ahead: Vec<String>,
+    behind: Vec<String>,
+    files: Vec<ChangedFile>,
+    options: DiffOptions,
+    term: String
+}
+
+impl CompareWindow {
+    pub fn new(from: &str, to: &str) -> CompareWindow {
+        CompareWindow {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            ahead: vec![],
+            behind: vec![],
+            files: vec![],
+            options: DiffOptions::default(),
+            term: "".to_owned()
+        }
+    }
+
+    fn symmetric_range(&self) -> String {
+        format!("{}...{}", self.from, self.to)
+    }
+
+    fn on_press_enter(&mut self, window: &mut Window) -> bool {
+        let line = window.cursor_line_value();
+        let line = line.trim_end();
+
+        if let Some(file) = self.files.iter_mut().find(|f| f.header() == line) {
+            file.expanded = !file.expanded;
+            self.render(window);
+            return true
+        }
+
+        let commit_line = line.trim_start();
+        let is_commit = self.ahead.iter().chain(self.behind.iter()).any(|c| c == commit_line);
+
+        if is_commit {
+            if let Some(hash) = commit_line.split(' ').next() {
+                Renderer::new(
+                    &mut DetailedCommitWindow::new(hash),
+                    ScreenSize::max(),
+                    Position::default(),
+                    screen()
+                ).render();
+            }
+        }
+
+        true
+    }
+
+    // Esc stops a search first, then collapses the files and closes
+    // the window last.
+    fn on_press_esc(&mut self, window: &mut Window) -> bool {
+        if !self.term.is_empty() {
+            return self.search_stop(window)
+        }
+
+        if !self.files.iter().any(|f| f.expanded) {
+            return false
+        }
+
+        for file in self.files.iter_mut() { file.expanded = false; }
+        self.render(window);
+
+        true
+    }
+
+    fn render(&mut self, window: &mut Window) {
+        let range = self.symmetric_range();
+
+        let mut lines: Vec<Line> = vec![
+            Line::new(vec![
+                Part::new("Compare:", Some(vec![Style::Bold, Style::Underlined])),
+                Part::plain(&format!(" {} with {}", self.from, self.to))
+            ]),
+            diff_options_header(&self.options),
+            Line::empty()
+        ];
+
+        let commit_sections = [
+            (format!("Commits in {}..{}: ({})", self.from, self.to, self.ahead.len()), &self.ahead),
+            (format!("Commits in {}..{}: ({})", self.to, self.from, self.behind.len()), &self.behind)
+        ];
+
+        for (title, commits) in commit_sections {
+            lines.push(Line::from_string(title, Some(vec![Style::Bold, Style::Underlined])));
+            lines.extend(commits.iter().map(|c| Line::plain(&format!("  {}", c))));
+            lines.push(Line::empty());
+        }
+
+        lines.push(Line::from_string(
+            format!("Files changed in {}: ({})", range, self.files.len()),
+            Some(vec![Style::Bold, Style::Underlined])
+        ));
+
+        let options = &self.options;
+        for file in self.files.iter_mut() {
+            lines.push(Line::new(vec![
+                Part::new(&file.header(), Some(vec![Style::Bold]))
+            ]));
+
+            if !file.expanded { continue }
+
+            let (from, path) = (file.from.as_deref(), &file.path);
+            let diff = file.diff.get_or_insert_with(|| git::diff_range_file(&range, from, path, options));
+            lines.extend(file.rendered.render(diff, Some(path), DiffLayout::Unified, window.width()));
+            lines.push(Line::empty());
+        }
+
+        window.set_lines(lines);
+    }
+}
+
+impl Component<CompareWindow> for CompareWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        // Rows have to match the lines for enter to find the file.
+        window.set_wrap(false);
+
+        let non_empty = |lines: Vec<String>| -> Vec<String> {
+            lines.into_iter().filter(|l| !l.is_empty()).collect()
+        };
+
+        self.ahead  = non_empty(git::log_range(&format!("{}..{}", self.from, self.to)));
+        self.behind = non_empty(git::log_range(&format!("{}..{}", self.to, self.from)));
+
+        let files: Vec<ChangedFile> = non_empty(git::diff_range_name_status(&self.symmetric_range(), &self.options))
+            .iter()
+            .filter_map(|l| {
+                // Renames and copies list both paths, the last one is the
+                // path on the compared side.
+                let columns: Vec<&str> = l.split('\t').collect();
+                let status = columns.first()?.to_string();
+                let path = columns.last()?.to_string();
+                let from = (columns.len() > 2).then(|| columns[1].to_owned());
+
+                // Keep the files which were already expanded open.
+                let expanded = self.files.iter().any(|f| f.path == path && f.expanded);
+
+                Some(ChangedFile { status, from, path, expanded, diff: None, rendered: DiffCache::default() })
+            })
+            .collect();
+
+        self.files = files;
+
+        self.render(window);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<CompareWindow>) {
+        handlers.insert(KEY_LF, CompareWindow::on_press_enter);
+
+        // Replaces the Esc handler of the search.
+        register_search_handlers(handlers);
+        register_diff_option_handlers(handlers);
+        handlers.insert(KEY_ETB, CompareWindow::on_press_esc);
+    }
+}
+
+impl DiffableComponent<CompareWindow> for CompareWindow {
+    fn diff_options(&mut self) -> &mut DiffOptions {
+        &mut self.options
+    }
+}
+
+impl SearchableComponent<CompareWindow> for CompareWindow {
+    fn term(&self) -> String {
+        self.term.clone()
+    }
+
+    fn set_term(&mut self, term: String) {
+        self.term = term;
+    }
+}
@@ -1,17 +1,82 @@
-use std::path::Path;
-use std::collections::HashMap;
-
 use crate::git;
-use crate::gitko::diff_display::color_diff_line;
+use crate::git::DiffOptions;
+use crate::diffable::{DiffableComponent, diff_options_header, register_diff_option_handlers};
+use crate::gitko::diff_display::{DiffCache, DiffLayout};
 
-use gitko_common::ascii_table::{KEY_LF, KEY_ETB};
+use gitko_common::ascii_table::{KEY_LF, KEY_ETB, KEY_S_LOWER};
 use gitko_render::{Component, KeyHandlers, Line, Window, Part, Style};
 
+enum ChangeType {
+    Modified,
+    Added,
+    Deleted,
+    Renamed(String),
+    Copied(String),
+    ModeChanged
+}
+
+impl ChangeType {
+    fn label(&self) -> &str {
+        match self {
+            ChangeType::Modified    => "modified",
+            ChangeType::Added       => "added",
+            ChangeType::Deleted     => "deleted",
+            ChangeType::Renamed(_)  => "renamed",
+            ChangeType::Copied(_)   => "copied",
+            ChangeType::ModeChanged => "mode"
+        }
+    }
+}
+
+struct FileChange {
+    path: String,
+    change_type: ChangeType,
+    // None when numstat has no line for the file or it is binary.
+    additions: Option<u32>,
+    deletions: Option<u32>,
+    // numstat reports binary files as '-' for both counts.
+    binary: bool,
+    lines: Vec<String>,
+    rendered: DiffCache,
+    expanded: bool
+}
+
+impl FileChange {
+    fn new(path: String) -> FileChange {
+        FileChange {
+            path,
+            change_type: ChangeType::Modified,
+            additions: None,
+            deletions: None,
+            binary: false,
+            lines: vec![],
+            rendered: DiffCache::default(),
+            expanded: true
+        }
+    }
+
+    fn header(&self) -> String {
+        let path = match &self.change_type {
+            ChangeType::Renamed(from) | ChangeType::Copied(from) => format!("{} -> {}", from, self.path),
+            _ => self.path.clone()
+        };
+
+        let stats = match (self.additions, self.deletions) {
+            (Some(additions), Some(deletions)) => format!("  +{} -{}", additions, deletions),
+            _ if self.binary                   => "  binary".to_owned(),
+            _                                  => "".to_owned()
+        };
+
+        format!("{:<9}{}{}", self.change_type.label(), path, stats)
+    }
+}
+
 pub struct DetailedCommitWindow {
     commit_hash: String,
     commit_details: Vec<String>,
-    file_changes: HashMap<String, Vec<String>>,
-    expanded_changes: HashMap<String, bool>,
+    file_changes: Vec<FileChange>,
+    layout: DiffLayout,
+    options: DiffOptions
 }
 
 impl DetailedCommitWindow {
@@ -19,121 +84,204 @@ impl DetailedCommitWindow {
         DetailedCommitWindow{
             commit_hash: commit_hash.to_owned(),
             commit_details: vec![],
-            file_changes: HashMap::new(),
-            expanded_changes: HashMap::new(),
+            file_changes: vec![],
+            layout: DiffLayout::Unified,
+            options: DiffOptions::default()
         }
     }
 
     fn on_press_enter(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
-        let path = Path::new(&line);
-
-        if !path.exists() {
-            return true
-        }
+        let line = window.cursor_line_value();
+        let line = line.trim_end();
 
-        if let Some(path_str) = path.to_str().to_owned() {
-            match self.expanded_changes.get(path_str) {
-                Some(&true) => {
-                    self.expanded_changes
-                        .entry(path_str.to_string())
-                        .and_modify(|v| *v = false);
-                }
-                Some(&false) => {
-                    self.expanded_changes
-                        .entry(path_str.to_string())
-                        .and_modify(|v| *v = true);
-                }
-                None => ()
-            };
-
-            self.on_start(window);
+        if let Some(change) = self.file_changes.iter_mut().find(|c| c.header() == line) {
+            change.expanded = !change.expanded;
+            self.render(window);
         }
 
         true
     }
 
     fn on_press_esc(&mut self, window: &mut Window) -> bool {
-        for (_, val) in self.expanded_changes.iter_mut() { *val = false; }
-        self.on_start(window);
+        for change in self.file_changes.iter_mut() { change.expanded = false; }
+        self.render(window);
         true
     }
-}
 
-impl Component<DetailedCommitWindow> for DetailedCommitWindow {
-    fn on_start(&mut self, window: &mut Window)  {
-        let details = git::show(&self.commit_hash);
+    fn toggle_layout(&mut self, window: &mut Window) -> bool {
+        self.layout = self.layout.toggle();
+        self.render(window);
+        true
+    }
 
-        let mut current_path: Option<String> = None;
+    fn load(&mut self) {
+        let details = git::show(&self.commit_hash, &self.options);
 
-        for i in 0..details.len() {
-            let line = &details[i];
+        let description_end = details
+            .iter()
+            .position(|l| l.starts_with("diff "))
+            .unwrap_or(details.len());
+        self.commit_details = details[..description_end].to_vec();
 
-            if line.starts_with("+++") {
-                let before = details[i - 1].trim_start_matches("--- ");
-                let after  = line.trim_start_matches("+++ ");
+        let mut changes: Vec<FileChange> = vec![];
+        let mut in_hunks = false;
 
-                let path = if before == "/dev/null" { after.to_string() }
-                           else                     { before.to_string() };
+        for line in &details[description_end..] {
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                // The header is ambiguous for paths containing " b/", the
+                // extended header lines below override it when present.
+                let path = paths
+                    .rsplit_once(" b/")
+                    .map(|(_, p)| p)
+                    .unwrap_or(paths);
 
-                let file_path_parts: Vec<&str> = path.split('/').collect();
-                let file_path: String = file_path_parts[1..]
-                    .iter()
-                    .map(|l| l.to_string())
-                    .collect::<Vec<String>>()
-                    .join("/");
+                changes.push(FileChange::new(path.to_owned()));
+                in_hunks = false;
+                continue
+            }
 
-                self.file_changes.insert(file_path.clone(), vec![]);
+            // Merge commits are shown as a combined diff of the file.
+            if let Some(path) = line.strip_prefix("diff --cc ").or_else(|| line.strip_prefix("diff --combined ")) {
+                changes.push(FileChange::new(path.to_owned()));
+                in_hunks = false;
+                continue
+            }
 
-                if !self.expanded_changes.contains_key(&file_path) {
-                    self.expanded_changes.insert(file_path.clone(), true);
-                }
+            let Some(change) = changes.last_mut() else { continue };
 
-                current_path = Some(file_path);
+            if in_hunks || line.starts_with("@@") || line.starts_with("Binary files") {
+                in_hunks = true;
+                change.lines.push(line.to_string());
+                continue
             }
 
-            if let Some(path) = &current_path {
-                if let Some(changes) = self.file_changes.get_mut(path) {
-                    changes.push(line.to_string())
-                }
+            if line.starts_with("new file mode") {
+                change.change_type = ChangeType::Added;
+            } else if line.starts_with("deleted file mode") {
+                change.change_type = ChangeType::Deleted;
+            } else if line.starts_with("old mode") {
+                change.change_type = ChangeType::ModeChanged;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                change.change_type = ChangeType::Renamed(from.to_owned());
+            } else if let Some(from) = line.strip_prefix("copy from ") {
+                change.change_type = ChangeType::Copied(from.to_owned());
+            } else if let Some(to) = line.strip_prefix("rename to ").or_else(|| line.strip_prefix("copy to ")) {
+                change.path = to.to_owned();
+            } else if let Some(path) = line.strip_prefix("+++ b/") {
+                change.path = path.to_owned();
             }
         }
 
-        let mut output: Vec<Line> = vec![];
+        // numstat lists the files in the same order as the patch above.
+        let stats: Vec<String> = git::show_numstat(&self.commit_hash, &self.options)
+            .into_iter()
+            .filter(|l| !l.is_empty())
+            .collect();
 
-        if let Some(description_end_position) = details.iter().position(|l| l.starts_with("diff")) {
-            self.commit_details = details[0..description_end_position].to_vec();
-            let mut description_lines = self.commit_details
-                    .iter()
-                    .map(|l| Line::from_str(l, None))
-                    .collect();
-            output.append(&mut description_lines)
+        if stats.len() == changes.len() {
+            for (change, stat) in changes.iter_mut().zip(stats.iter()) {
+                (change.additions, change.deletions, change.binary) = parse_numstat(stat);
+            }
         }
 
-        for key in self.file_changes.keys() {
+        // Keep the folding state of files which were already displayed.
+        for change in changes.iter_mut() {
+            if let Some(previous) = self.file_changes.iter().find(|c| c.path == change.path) {
+                change.expanded = previous.expanded;
+            }
+        }
+
+        self.file_changes = changes;
+    }
+
+    fn summary(&self) -> String {
+        let additions: u32 = self.file_changes.iter().filter_map(|c| c.additions).sum();
+        let deletions: u32 = self.file_changes.iter().filter_map(|c| c.deletions).sum();
+
+        let files = self.file_changes.len();
+        let plural = if files == 1 { "" } else { "s" };
+
+        format!("{} file{} changed, {} insertions(+), {} deletions(-)", files, plural, additions, deletions)
+    }
+
+    fn render(&mut self, window: &mut Window) {
+        let mut output: Vec<Line> = self.commit_details
+            .iter()
+            .map(|l| Line::from_str(l, None))
+            .collect();
+
+        output.push(Line::from_string(
+            self.summary(),
+            Some(vec![Style::Bold, Style::Underlined])
+        ));
+        output.push(diff_options_header(&self.options));
+        output.push(Line::empty());
+
+        let width = window.width();
+        for change in self.file_changes.iter_mut() {
             output.push(Line::new(
-                vec![Part::new(key, Some(vec![Style::Bold, Style::Underlined]))]
+                vec![Part::new(&change.header(), Some(vec![Style::Bold, Style::Underlined]))]
             ));
 
-            if let Some(expanded) = self.expanded_changes.get(key) {
-                if *expanded {
-                    if let Some(changes) = self.file_changes.get(key) {
-                        let mut change_lines = changes
-                            .iter()
-                            .map(|l| color_diff_line(l))
-                            .collect();
-                        output.append(&mut change_lines);
-                        output.push(Line::empty());
-                    }
-                }
+            if change.expanded {
+                output.extend(change.rendered.render(&change.lines, Some(&change.path), self.layout, width));
+                output.push(Line::empty());
             }
         }
 
         window.set_lines(output);
     }
+}
+
+// Additions, deletions and whether the file is binary from a numstat
+// line like '3\t1\tpath'.
+fn parse_numstat(line: &str) -> (Option<u32>, Option<u32>, bool) {
+    let mut columns = line.split('\t');
+    let (additions, deletions) = (columns.next(), columns.next());
+
+    (
+        additions.and_then(|c| c.parse().ok()),
+        deletions.and_then(|c| c.parse().ok()),
+        additions == Some("-") && deletions == Some("-")
+    )
+}
+
+impl Component<DetailedCommitWindow> for DetailedCommitWindow {
+    fn on_start(&mut self, window: &mut Window)  {
+        // Rows have to match the lines for enter to find the file.
+        window.set_wrap(false);
+
+        self.load();
+        self.render(window);
+    }
 
     fn register_handlers(&self, handlers: &mut KeyHandlers<DetailedCommitWindow>) {
         handlers.insert(KEY_ETB, DetailedCommitWindow::on_press_esc);
         handlers.insert(KEY_LF, DetailedCommitWindow::on_press_enter);
+        handlers.insert(KEY_S_LOWER, DetailedCommitWindow::toggle_layout);
+
+        register_diff_option_handlers(handlers);
+    }
+}
+
+impl DiffableComponent<DetailedCommitWindow> for DetailedCommitWindow {
+    fn diff_options(&mut self) -> &mut DiffOptions {
+        &mut self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_numstat;
+
+    #[test]
+    fn parse_numstat_reads_the_counts() {
+        assert_eq!(parse_numstat("12\t0\tsrc/main.rs"), (Some(12), Some(0), false));
+    }
+
+    #[test]
+    fn parse_numstat_labels_only_dashes_as_binary() {
+        assert_eq!(parse_numstat("-\t-\timage.png"), (None, None, true));
+        assert_eq!(parse_numstat("diff --git a/a b/a"), (None, None, false));
     }
 }
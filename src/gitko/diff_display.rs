@@ -1,4 +1,44 @@
-use gitko_render::{Line, Part};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use crate::gitko::syntax_highlight::Highlighter;
+use gitko_render::{Line, Part, Style, RGB};
+
+// Narrower terminals fall back to the unified layout, the two
+// columns would be too narrow to read anything.
+const MIN_SIDE_BY_SIDE_WIDTH: i32 = 100;
+const LINE_NUMBER_WIDTH: usize = 5;
+const SEPARATOR: &str = " | ";
+
+// Word diffs of very long lines are quadratic and rarely readable.
+const MAX_WORD_DIFF_TOKENS: usize = 300;
+
+const BACKGROUND: RGB = (0, 0, 0);
+const ADDED: RGB = (0, 255, 0);
+const REMOVED: RGB = (255, 0, 0);
+const ADDED_BACKGROUND: RGB = (0, 40, 0);
+const REMOVED_BACKGROUND: RGB = (50, 0, 0);
+const ADDED_STRONG_BACKGROUND: RGB = (0, 95, 0);
+const REMOVED_STRONG_BACKGROUND: RGB = (110, 0, 0);
+
+// Byte ranges of a line which differ from the line it is paired with.
+type Spans = Vec<Range<usize>>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffLayout {
+    Unified,
+    SideBySide
+}
+
+impl DiffLayout {
+    pub fn toggle(self) -> DiffLayout {
+        match self {
+            DiffLayout::Unified    => DiffLayout::SideBySide,
+            DiffLayout::SideBySide => DiffLayout::Unified
+        }
+    }
+}
 
 pub fn color_diff_line(line: &str) -> Line {
     if line.starts_with('+') {
@@ -25,7 +65,501 @@ pub fn color_diff_line(line: &str) -> Line {
                 (0, 0, 0)
             )
         ])
+    } else if line.starts_with("  >") {
+        // Commits added to a submodule, from '--submodule=log'.
+        Line::new(vec![Part::painted(line, (0, 255, 0), (0, 0, 0))])
+    } else if line.starts_with("  <") {
+        Line::new(vec![Part::painted(line, (255, 0, 0), (0, 0, 0))])
+    } else if line.starts_with("Submodule ") {
+        Line::from_str(line, Some(vec![Style::Bold]))
     } else {
         Line::plain(line)
     }
 }
+
+// Keeps a rendered diff until the diff or the way it is shown changes,
+// highlighting a large diff again on every render is slow.
+#[derive(Clone, Default)]
+pub struct DiffCache {
+    key: Option<u64>,
+    lines: Vec<Line>,
+    line_numbers: Vec<Option<usize>>
+}
+
+impl DiffCache {
+    pub fn render(&mut self, lines: &[String], path: Option<&str>, layout: DiffLayout, width: i32) -> Vec<Line> {
+        self.render_with_line_numbers(lines, path, layout, width).0
+    }
+
+    pub fn render_with_line_numbers(
+        &mut self,
+        lines: &[String],
+        path: Option<&str>,
+        layout: DiffLayout,
+        width: i32) -> (Vec<Line>, Vec<Option<usize>>) {
+        let key = cache_key((lines, path, layout == DiffLayout::SideBySide, width));
+
+        if self.key != Some(key) {
+            let (rendered, line_numbers) = render_diff_with_line_numbers(lines, path, layout, width);
+            *self = DiffCache { key: Some(key), lines: rendered, line_numbers };
+        }
+
+        (self.lines.clone(), self.line_numbers.clone())
+    }
+
+    pub fn highlight_file(&mut self, lines: &[String], path: &str) -> Vec<Line> {
+        let key = cache_key((lines, path));
+
+        if self.key != Some(key) {
+            *self = DiffCache { key: Some(key), lines: highlight_file(lines, path), line_numbers: vec![] };
+        }
+
+        self.lines.clone()
+    }
+}
+
+fn cache_key(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Also returns the line in the new version of the file each rendered
+// line belongs to, starting at 1. Removed lines belong to the line
+// they were removed at, file headers to no line.
+fn render_diff_with_line_numbers(
+    lines: &[String],
+    path: Option<&str>,
+    layout: DiffLayout,
+    width: i32) -> (Vec<Line>, Vec<Option<usize>>) {
+    let side_by_side = layout == DiffLayout::SideBySide && width >= MIN_SIDE_BY_SIDE_WIDTH;
+
+    let mut state = DiffRenderer {
+        column_width: if side_by_side { (width as usize - SEPARATOR.len() - 1) / 2 } else { 0 },
+        side_by_side,
+        old_line: 0,
+        new_line: 0,
+        old_highlighter: Highlighter::for_path(path),
+        new_highlighter: Highlighter::for_path(path),
+        removed: vec![],
+        added: vec![],
+        output: vec![],
+        line_numbers: vec![]
+    };
+
+    let mut in_hunk = false;
+
+    for line in lines {
+        if line.starts_with("@@") {
+            state.flush();
+
+            if let Some((old_line, new_line)) = parse_hunk_header(line) {
+                state.old_line = old_line;
+                state.new_line = new_line;
+                in_hunk = true;
+            }
+
+            let number = if in_hunk { Some(state.new_line) } else { None };
+            state.push(color_diff_line(line), number);
+            continue
+        }
+
+        // Everything before the first hunk of a file is the file header.
+        if !in_hunk || line.starts_with("diff ") {
+            state.flush();
+            in_hunk = false;
+
+            if let Some((_, path)) = line.strip_prefix("diff --git ").and_then(|p| p.rsplit_once(" b/")) {
+                state.old_highlighter = Highlighter::for_path(Some(path));
+                state.new_highlighter = Highlighter::for_path(Some(path));
+            }
+
+            state.push(color_diff_line(line), None);
+            continue
+        }
+
+        if let Some(removed) = line.strip_prefix('-') {
+            // A removal after additions starts a new change block.
+            if !state.added.is_empty() { state.flush(); }
+
+            let colors = state.old_highlighter.highlight(removed);
+            state.removed.push(Change::new(state.old_line, removed, colors));
+            state.old_line += 1;
+        } else if let Some(added) = line.strip_prefix('+') {
+            let colors = state.new_highlighter.highlight(added);
+            state.added.push(Change::new(state.new_line, added, colors));
+            state.new_line += 1;
+        } else if let Some(context) = line.strip_prefix(' ') {
+            state.flush();
+            state.context(context);
+
+            state.old_line += 1;
+            state.new_line += 1;
+        } else {
+            state.flush();
+            state.push(Line::plain(line), None);
+        }
+    }
+
+    state.flush();
+    (state.output, state.line_numbers)
+}
+
+// Highlights the content of a whole file, used for files which have
+// no diff to show, like untracked files.
+pub fn highlight_file(lines: &[String], path: &str) -> Vec<Line> {
+    let mut highlighter = Highlighter::for_path(Some(path));
+
+    lines
+        .iter()
+        .map(|l| {
+            let change = Change::new(0, l, highlighter.highlight(l));
+            Line::new(paint(&change, None))
+        })
+        .collect()
+}
+
+struct Palette {
+    foreground: RGB,
+    background: RGB,
+    strong_background: RGB
+}
+
+const REMOVED_PALETTE: Palette = Palette {
+    foreground: REMOVED,
+    background: REMOVED_BACKGROUND,
+    strong_background: REMOVED_STRONG_BACKGROUND
+};
+
+const ADDED_PALETTE: Palette = Palette {
+    foreground: ADDED,
+    background: ADDED_BACKGROUND,
+    strong_background: ADDED_STRONG_BACKGROUND
+};
+
+struct Change {
+    number: usize,
+    text: String,
+    spans: Spans,
+    // Syntax highlighting foreground colors, empty if the language
+    // is not known.
+    colors: Vec<(Range<usize>, RGB)>
+}
+
+impl Change {
+    fn new(number: usize, text: &str, colors: Vec<(Range<usize>, RGB)>) -> Change {
+        Change { number, text: text.to_owned(), spans: vec![], colors }
+    }
+}
+
+struct DiffRenderer {
+    column_width: usize,
+    side_by_side: bool,
+    old_line: usize,
+    new_line: usize,
+    old_highlighter: Highlighter,
+    new_highlighter: Highlighter,
+    removed: Vec<Change>,
+    added: Vec<Change>,
+    output: Vec<Line>,
+    line_numbers: Vec<Option<usize>>
+}
+
+impl DiffRenderer {
+    fn push(&mut self, line: Line, number: Option<usize>) {
+        self.output.push(line);
+        self.line_numbers.push(number);
+    }
+
+    fn flush(&mut self) {
+        // Removed and added lines are paired in order, the i-th removed
+        // line is assumed to have become the i-th added line.
+        for (removed, added) in self.removed.iter_mut().zip(self.added.iter_mut()) {
+            if let Some((removed_spans, added_spans)) = word_diff(&removed.text, &added.text) {
+                removed.spans = removed_spans;
+                added.spans = added_spans;
+            }
+        }
+
+        // The new line the block starts at, added lines were counted already.
+        let block_start = self.new_line - self.added.len();
+
+        if self.side_by_side {
+            let rows = self.removed.len().max(self.added.len());
+
+            for i in 0..rows {
+                let mut parts = self.column(self.removed.get(i), Some(&REMOVED_PALETTE));
+                parts.push(Part::plain(SEPARATOR));
+                parts.extend(self.column(self.added.get(i), Some(&ADDED_PALETTE)));
+
+                let number = self.added.get(i).map(|c| c.number).unwrap_or(block_start);
+                self.push(Line::new(parts), Some(number));
+            }
+        } else {
+            let removed: Vec<Line> = self.removed
+                .iter()
+                .map(|c| unified_line('-', c, Some(&REMOVED_PALETTE)))
+                .collect();
+            let added: Vec<(Line, usize)> = self.added
+                .iter()
+                .map(|c| (unified_line('+', c, Some(&ADDED_PALETTE)), c.number))
+                .collect();
+
+            for line in removed {
+                self.push(line, Some(block_start));
+            }
+
+            for (line, number) in added {
+                self.push(line, Some(number));
+            }
+        }
+
+        self.removed.clear();
+        self.added.clear();
+    }
+
+    fn context(&mut self, text: &str) {
+        // Both sides have to see the context to keep the parser state right.
+        let old = Change::new(self.old_line, text, self.old_highlighter.highlight(text));
+        let new = Change::new(self.new_line, text, self.new_highlighter.highlight(text));
+
+        if !self.side_by_side {
+            self.push(unified_line(' ', &new, None), Some(new.number));
+            return
+        }
+
+        let mut parts = self.column(Some(&old), None);
+        parts.push(Part::plain(SEPARATOR));
+        parts.extend(self.column(Some(&new), None));
+
+        self.push(Line::new(parts), Some(new.number));
+    }
+
+    // Context lines are passed without a palette.
+    fn column(&self, change: Option<&Change>, palette: Option<&Palette>) -> Vec<Part> {
+        let text_width = self.column_width.saturating_sub(LINE_NUMBER_WIDTH + 1);
+
+        let Some(change) = change else {
+            return vec![Part::plain(&" ".repeat(LINE_NUMBER_WIDTH + 1 + text_width))]
+        };
+
+        let mut parts = vec![
+            Part::plain(&format!("{:>width$} ", change.number, width = LINE_NUMBER_WIDTH))
+        ];
+
+        // Tabs are expanded and the text is cut to the column width
+        // part by part, so the highlighted spans stay aligned.
+        let mut length = 0;
+        for mut part in paint(change, palette) {
+            part.value = part.value
+                .replace('\t', "    ")
+                .chars()
+                .take(text_width - length)
+                .collect();
+
+            length += part.value.chars().count();
+            parts.push(part);
+
+            if length >= text_width { break }
+        }
+
+        parts.push(Part::plain(&" ".repeat(text_width - length)));
+
+        parts
+    }
+}
+
+fn unified_line(prefix: char, change: &Change, palette: Option<&Palette>) -> Line {
+    let prefix = match palette {
+        Some(palette) => Part::painted(&prefix.to_string(), palette.foreground, line_background(change, palette)),
+        None          => Part::plain(&prefix.to_string())
+    };
+
+    let mut parts = vec![prefix];
+    parts.extend(paint(change, palette));
+
+    Line::new(parts)
+}
+
+// Highlighted lines keep their syntax colors, so the added or removed
+// state is shown by the background instead.
+fn line_background(change: &Change, palette: &Palette) -> RGB {
+    if change.colors.is_empty() { BACKGROUND } else { palette.background }
+}
+
+fn paint(change: &Change, palette: Option<&Palette>) -> Vec<Part> {
+    let text = &change.text;
+
+    let mut boundaries: Vec<usize> = vec![0, text.len()];
+    boundaries.extend(change.spans.iter().flat_map(|s| [s.start, s.end]));
+    boundaries.extend(change.colors.iter().flat_map(|(r, _)| [r.start, r.end]));
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    if text.is_empty() {
+        return vec![Part::plain("")]
+    }
+
+    boundaries
+        .windows(2)
+        .map(|w| {
+            let segment = &text[w[0]..w[1]];
+            let changed = change.spans.iter().any(|s| s.contains(&w[0]));
+            let syntax_color = change.colors
+                .iter()
+                .find(|(r, _)| r.contains(&w[0]))
+                .map(|(_, c)| *c);
+
+            match (palette, syntax_color) {
+                (Some(palette), _) => {
+                    let background = if changed { palette.strong_background }
+                                     else       { line_background(change, palette) };
+
+                    Part::painted(segment, syntax_color.unwrap_or(palette.foreground), background)
+                }
+                (None, Some(color)) => Part::painted(segment, color, BACKGROUND),
+                (None, None)        => Part::plain(segment)
+            }
+        })
+        .collect()
+}
+
+// Words, whitespace runs and single punctuation characters, as byte ranges.
+fn tokenize(text: &str) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = vec![];
+
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' { 0 }
+        else if c.is_whitespace()         { 1 }
+        else                              { 2 }
+    };
+
+    let mut previous_class = None;
+    for (index, c) in text.char_indices() {
+        let current_class = class(c);
+        let end = index + c.len_utf8();
+
+        match tokens.last_mut() {
+            Some(token) if previous_class == Some(current_class) && current_class != 2 => token.end = end,
+            _ => tokens.push(index..end)
+        }
+
+        previous_class = Some(current_class);
+    }
+
+    tokens
+}
+
+// Returns the changed byte ranges of both lines, or nothing when the
+// lines have nothing in common and highlighting would not help.
+fn word_diff(old: &str, new: &str) -> Option<(Spans, Spans)> {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+
+    if old_tokens.len() > MAX_WORD_DIFF_TOKENS || new_tokens.len() > MAX_WORD_DIFF_TOKENS {
+        return None
+    }
+
+    let old_words: Vec<&str> = old_tokens.iter().map(|t| &old[t.clone()]).collect();
+    let new_words: Vec<&str> = new_tokens.iter().map(|t| &new[t.clone()]).collect();
+
+    // Longest common subsequence table, lcs[i][j] is the length for
+    // the suffixes starting at i and j.
+    let mut lcs = vec![vec![0usize; new_words.len() + 1]; old_words.len() + 1];
+    for i in (0..old_words.len()).rev() {
+        for j in (0..new_words.len()).rev() {
+            lcs[i][j] = if old_words[i] == new_words[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    if lcs[0][0] == 0 { return None }
+
+    let mut old_changed = vec![];
+    let mut new_changed = vec![];
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_words.len() || j < new_words.len() {
+        if i < old_words.len() && j < new_words.len() && old_words[i] == new_words[j] {
+            i += 1;
+            j += 1;
+        } else if j < new_words.len() && (i == old_words.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            new_changed.push(new_tokens[j].clone());
+            j += 1;
+        } else {
+            old_changed.push(old_tokens[i].clone());
+            i += 1;
+        }
+    }
+
+    Some((merge_ranges(old_changed), merge_ranges(new_changed)))
+}
+
+fn merge_ranges(ranges: Spans) -> Spans {
+    let mut merged: Spans = vec![];
+
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => merged.push(range)
+        }
+    }
+
+    merged
+}
+
+// Parses the starting line numbers out of a '@@ -a,b +c,d @@' hunk header.
+pub fn parse_hunk_header(line: &str) -> Option<(usize, usize)> {
+    let mut ranges = line.trim_start_matches('@').split_whitespace();
+
+    let old = ranges.next()?.trim_start_matches('-');
+    let new = ranges.next()?.trim_start_matches('+');
+
+    let start = |range: &str| range.split(',').next()?.parse::<usize>().ok();
+
+    Some((start(old)?, start(new)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{word_diff, Spans};
+
+    fn changed<'a>(text: &'a str, spans: &Spans) -> Vec<&'a str> {
+        spans.iter().map(|s| &text[s.clone()]).collect()
+    }
+
+    #[test]
+    fn word_diff_marks_only_the_changed_words() {
+        let (old, new) = ("let count = 1;", "let total = 2;");
+        let (old_spans, new_spans) = word_diff(old, new).unwrap();
+
+        assert_eq!(changed(old, &old_spans), vec!["count", "1"]);
+        assert_eq!(changed(new, &new_spans), vec!["total", "2"]);
+    }
+
+    #[test]
+    fn word_diff_merges_adjacent_changes() {
+        let (old, new) = ("call(a)", "call(b, c)");
+        let (old_spans, new_spans) = word_diff(old, new).unwrap();
+
+        assert_eq!(changed(old, &old_spans), vec!["a"]);
+        assert_eq!(changed(new, &new_spans), vec!["b, c"]);
+    }
+
+    #[test]
+    fn word_diff_works_on_characters_not_bytes() {
+        let (old, new) = ("naïve café", "naïve thé");
+        let (old_spans, new_spans) = word_diff(old, new).unwrap();
+
+        assert_eq!(changed(old, &old_spans), vec!["café"]);
+        assert_eq!(changed(new, &new_spans), vec!["thé"]);
+    }
+
+    #[test]
+    fn word_diff_gives_up_on_unrelated_lines() {
+        assert_eq!(word_diff("alpha", "beta"), None);
+    }
+}
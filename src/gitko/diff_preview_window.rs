@@ -0,0 +1,213 @@
+use std::any::Any;
+use std::fs;
+
+use crate::git;
+use crate::git::{DiffOptions, FileState};
+use crate::gitko::binary_display::{binary_summary, guess_mime, is_binary};
+use crate::gitko::diff_display::{DiffCache, DiffLayout};
+use crate::gitko::diff_window::{configured_line_limit, DiffSource};
+use gitko_render::{Component, KeyHandlers, Line, Part, Style, TaskResult, Window};
+
+use gitko_common::ascii_table::{KEY_J_LOWER, KEY_K_LOWER};
+
+// Rendered previews of this many files are kept, moving the cursor back
+// to one of them only highlights it again if its diff changed.
+const CACHED_PREVIEWS: usize = 16;
+
+// The file a preview shows, sent by the status as
+// Option<PreviewFile> whenever the cursor moves to another line.
+#[derive(Clone, PartialEq)]
+pub struct PreviewFile {
+    pub path: String,
+    pub file_state: FileState,
+    pub source: DiffSource
+}
+
+// Sent to scroll the preview without focusing it.
+pub enum ScrollPreview {
+    Up,
+    Down
+}
+
+// Changes of the file under the cursor of the status, shown next to it.
+pub struct DiffPreviewWindow {
+    file: Option<PreviewFile>,
+    // Counts the loads, results of an older one which arrive after a
+    // newer one started are dropped.
+    load: usize,
+    // A different file starts at the top, the same one keeps its
+    // position when it is loaded again after a change.
+    scroll_to_top: bool,
+    // Most recently shown last.
+    cache: Vec<(PreviewFile, DiffCache)>
+}
+
+impl DiffPreviewWindow {
+    pub fn new() -> DiffPreviewWindow {
+        DiffPreviewWindow {
+            file: None,
+            load: 0,
+            scroll_to_top: true,
+            cache: vec![]
+        }
+    }
+
+    fn show(&mut self, window: &mut Window, file: Option<PreviewFile>) {
+        self.scroll_to_top = file != self.file;
+        self.file = file;
+        self.load += 1;
+
+        let Some(file) = self.file.clone() else {
+            window.set_lines(vec![
+                Line::new(vec![Part::painted("Move the cursor to a file to see its changes.", (128, 128, 128), (0, 0, 0))])
+            ]);
+            window.move_to_line(0);
+            return
+        };
+
+        let cache = self.cache
+            .iter()
+            .position(|(f, _)| *f == file)
+            .map(|i| self.cache.remove(i).1)
+            .unwrap_or_default();
+
+        let (load, width) = (self.load, window.width());
+        window.spawn_task(&format!("Loading {}", file.path), move || {
+            let mut cache = cache;
+            let lines = load_preview(&file, width, &mut cache);
+            (load, lines, file, cache)
+        });
+    }
+
+    fn scroll_down(&mut self, window: &mut Window) -> bool {
+        window.move_screen_down(1);
+        true
+    }
+
+    fn scroll_up(&mut self, window: &mut Window) -> bool {
+        window.move_screen_up(1);
+        true
+    }
+
+    fn jump_down(&mut self, window: &mut Window) -> bool {
+        for _ in 0..20 {
+            window.move_screen_down(1);
+        }
+
+        true
+    }
+
+    fn jump_up(&mut self, window: &mut Window) -> bool {
+        for _ in 0..20 {
+            window.move_screen_up(1);
+        }
+
+        true
+    }
+}
+
+// Diffs are read and highlighted in the background, the cursor of
+// the status keeps moving meanwhile.
+fn load_preview(file: &PreviewFile, width: i32, cache: &mut DiffCache) -> Vec<Line> {
+    let limit = configured_line_limit();
+
+    let (description, content) = match (file.file_state, file.source) {
+        (FileState::Untracked, _) => ("untracked", vec![]),
+        (_, DiffSource::Index)    => ("staged", git::diff_staged_file(&file.path, &DiffOptions::default())),
+        (_, DiffSource::Worktree) => ("unstaged", git::diff_file(&file.path, &DiffOptions::default()))
+    };
+
+    let mut lines = vec![
+        Line::new(vec![
+            Part::new(&file.path, Some(vec![Style::Bold, Style::Underlined])),
+            Part::plain(&format!(" ({})", description))
+        ]),
+        Line::empty()
+    ];
+
+    let total = if file.file_state == FileState::Untracked {
+        let bytes = fs::read(&file.path).unwrap_or_default();
+
+        if is_binary(&bytes) {
+            let mime = guess_mime(&file.path, &bytes);
+            lines.extend(binary_summary(&file.path, mime, None, Some(bytes.len() as u64)));
+            return lines
+        }
+
+        let content: Vec<String> = String::from_utf8_lossy(&bytes).lines().map(str::to_owned).collect();
+        lines.extend(cache.highlight_file(&content[..content.len().min(limit)], &file.path));
+        content.len()
+    } else {
+        // The diff ends with an empty line.
+        if content.iter().all(|l| l.is_empty()) {
+            lines.push(Line::plain("No changes."));
+            return lines
+        }
+
+        lines.extend(cache.render(&content[..content.len().min(limit)], Some(&file.path), DiffLayout::Unified, width));
+        content.len()
+    };
+
+    if total > limit {
+        lines.push(Line::from_string(
+            format!("... {} more lines (enter: open the diff)", total - limit),
+            Some(vec![Style::Bold])
+        ));
+    }
+
+    lines
+}
+
+impl Component<DiffPreviewWindow> for DiffPreviewWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        window.show_cursor(false);
+
+        let file = self.file.clone();
+        self.show(window, file);
+    }
+
+    fn on_exit(&mut self, window: &mut Window) {
+        window.show_cursor(true);
+    }
+
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        let Ok(result) = result.downcast::<(usize, Vec<Line>, PreviewFile, DiffCache)>() else { return };
+
+        let (load, lines, file, cache) = *result;
+
+        self.cache.push((file, cache));
+        if self.cache.len() > CACHED_PREVIEWS {
+            self.cache.remove(0);
+        }
+
+        if load != self.load { return }
+
+        window.set_lines(lines);
+
+        if self.scroll_to_top {
+            window.move_to_line(0);
+        }
+    }
+
+    fn on_pane_message(&mut self, window: &mut Window, message: &dyn Any) {
+        if let Some(file) = message.downcast_ref::<Option<PreviewFile>>() {
+            self.show(window, file.clone());
+        }
+
+        // Half of the preview at a time, the status keeps the focus.
+        let scroll = (window.height() / 2).max(1);
+        match message.downcast_ref::<ScrollPreview>() {
+            Some(ScrollPreview::Down) => (0..scroll).for_each(|_| window.move_screen_down(1)),
+            Some(ScrollPreview::Up)   => (0..scroll).for_each(|_| window.move_screen_up(1)),
+            None => {}
+        }
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<DiffPreviewWindow>) {
+        handlers.insert(KEY_J_LOWER, DiffPreviewWindow::scroll_down);
+        handlers.insert(KEY_K_LOWER, DiffPreviewWindow::scroll_up);
+
+        handlers.insert(4, DiffPreviewWindow::jump_down);
+        handlers.insert(21, DiffPreviewWindow::jump_up);
+    }
+}
@@ -1,24 +1,81 @@
-use std::{fs::File, io::{BufReader, BufRead}};
+use std::fs;
 
 use crate::git;
-use crate::git::FileState;
-use crate::gitko::diff_display::color_diff_line;
+use crate::git::{DiffOptions, FileState};
+use crate::diffable::{DiffableComponent, diff_options_header, register_diff_option_handlers};
+use crate::gitko::binary_display::{binary_summary, guess_mime, hexdump, hexdump_line_count, is_binary};
+use crate::gitko::diff_display::{DiffCache, DiffLayout, parse_hunk_header};
+use crate::gitko::output_window::OutputWindow;
+use crate::editor::open_in_editor;
 use crate::searchable::{SearchableComponent, register_search_handlers};
-use gitko_render::{KeyHandlers, Component, Window};
+use crate::{screen, max_height};
+use gitko_render::{KeyHandlers, Component, Window, Line, Part, Position, Renderer, ScreenSize, Style, TaskResult};
 
-use gitko_common::ascii_table::{KEY_J_LOWER, KEY_K_LOWER};
+use gitko_common::ascii_table::{KEY_E_LOWER, KEY_I_LOWER, KEY_J_LOWER, KEY_K_LOWER, KEY_M_LOWER, KEY_S_LOWER,
+                                KEY_T_LOWER, KEY_U_LOWER, KEY_X_LOWER};
+
+// Rendering huge generated files freezes the screen, so only this many
+// lines are shown at first. Can be changed with 'git config gitko.diffLineLimit'.
+const DEFAULT_LINE_LIMIT: usize = 2000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffSource {
+    // Changes in the worktree which are not staged yet.
+    Worktree,
+    // Changes staged in the index, compared to HEAD.
+    Index
+}
+
+impl DiffSource {
+    fn description(&self) -> &str {
+        match self {
+            DiffSource::Worktree => "unstaged changes (worktree vs index)",
+            DiffSource::Index    => "staged changes (index vs HEAD)"
+        }
+    }
+}
 
 pub struct DiffWindow {
     path: String,
     file_state: FileState,
+    source: DiffSource,
+    partially_staged: bool,
+    diff: Vec<String>,
+    binary: Option<Vec<Line>>,
+    bytes: Option<Vec<u8>>,
+    hexdump: bool,
+    line_limit: usize,
+    // Line of the file each line of the window belongs to, starting at 1.
+    line_numbers: Vec<Option<usize>>,
+    // Lines of the window showing a hunk header, in the order of the hunks.
+    hunk_rows: Vec<usize>,
+    layout: DiffLayout,
+    rendered: DiffCache,
+    options: DiffOptions,
+    // Counts the loads, results of an older one which arrive after a
+    // newer one started are dropped.
+    load: usize,
     term: String
 }
 
 impl DiffWindow {
-    pub fn new(path: &str, file_state: FileState) -> DiffWindow {
+    pub fn new(path: &str, file_state: FileState, source: DiffSource) -> DiffWindow {
         DiffWindow {
             path: path.to_string(),
             file_state,
+            source,
+            partially_staged: false,
+            diff: vec![],
+            binary: None,
+            bytes: None,
+            hexdump: false,
+            line_limit: configured_line_limit(),
+            line_numbers: vec![],
+            hunk_rows: vec![],
+            layout: DiffLayout::Unified,
+            rendered: DiffCache::default(),
+            options: DiffOptions::default(),
+            load: 0,
             term: "".to_owned()
         }
     }
@@ -48,38 +105,210 @@ impl DiffWindow {
 
         true
     }
+
+    // Only files with both staged and unstaged changes have
+    // anything to switch to.
+    fn toggle_source(&mut self, window: &mut Window) -> bool {
+        if !self.partially_staged { return true }
+
+        self.source = match self.source {
+            DiffSource::Worktree => DiffSource::Index,
+            DiffSource::Index    => DiffSource::Worktree
+        };
+
+        self.on_start(window);
+        true
+    }
+
+    fn toggle_layout(&mut self, window: &mut Window) -> bool {
+        self.layout = self.layout.toggle();
+        self.render(window);
+        true
+    }
+
+    fn toggle_hexdump(&mut self, window: &mut Window) -> bool {
+        self.hexdump = !self.hexdump;
+
+        if self.hexdump && self.bytes.is_none() {
+            self.bytes = Some(new_content(&self.path, self.source));
+        }
+
+        self.render(window);
+        true
+    }
+
+    // Opens the worktree version of the file at the line under the
+    // cursor, mapped through the hunk headers for diffs.
+    fn open_editor(&mut self, window: &mut Window) -> bool {
+        if !std::path::Path::new(&self.path).is_file() { return true }
+
+        let line = self.line_numbers
+            .get(window.cursor_line_index())
+            .copied()
+            .flatten();
+
+        open_in_editor(&self.path, line);
+
+        self.on_start(window);
+        true
+    }
+
+    fn stage_hunk(&mut self, window: &mut Window) -> bool {
+        if self.source != DiffSource::Worktree { return true }
+        self.apply_hunk(window, false)
+    }
+
+    fn unstage_hunk(&mut self, window: &mut Window) -> bool {
+        if self.source != DiffSource::Index { return true }
+        self.apply_hunk(window, true)
+    }
+
+    // The patch is cut out of the diff on screen, so it was made with
+    // the same context and whitespace options as what is shown.
+    fn apply_hunk(&mut self, window: &mut Window, reverse: bool) -> bool {
+        if self.file_state == FileState::Untracked || self.binary.is_some() || self.hexdump { return true }
+
+        let cursor = window.cursor_line_index();
+        let Some(hunk) = self.hunk_rows.iter().rposition(|row| *row <= cursor) else { return true };
+
+        // Ignoring whitespace takes the context from the worktree, the
+        // index has to match it to stage. Unstaging compares with the
+        // index, which is where the staged diff takes its context from.
+        let index: Option<Vec<String>> = (!reverse).then(|| {
+            String::from_utf8_lossy(&git::object_bytes(&format!(":{}", self.path)))
+                .lines()
+                .map(str::to_owned)
+                .collect()
+        });
+
+        let Some(patch) = hunk_patch(&self.diff, hunk, index.as_deref()) else { return true };
+
+        let errors = git::apply_to_index(&patch, reverse, &self.options);
+
+        self.on_start(window);
+        show_errors(window, errors);
+        true
+    }
+
+    fn load_more(&mut self, window: &mut Window) -> bool {
+        self.line_limit += configured_line_limit();
+        self.render(window);
+        true
+    }
+
+    // The result is shown by on_task_done, only the last load started
+    // is shown.
+    fn load(&mut self, window: &mut Window) {
+        self.load += 1;
+
+        let (load, path, file_state, source, options) =
+            (self.load, self.path.clone(), self.file_state, self.source, self.options.clone());
+
+        window.spawn_task(&format!("Loading {}", self.path), move || {
+            let loaded = match file_state {
+                FileState::Untracked => load_untracked(&path),
+                _ => load_diff(&path, source, &options)
+            };
+            (load, loaded)
+        });
+    }
+
+    // Returns the lines to show and the file line each of them belongs to.
+    fn body(&mut self, window: &Window) -> (Vec<Line>, Vec<Option<usize>>) {
+        if self.hexdump {
+            let bytes = self.bytes.as_deref().unwrap_or_default();
+            let mut lines: Vec<Line> = hexdump(bytes, self.line_limit)
+                .iter()
+                .map(|l| Line::plain(l))
+                .collect();
+
+            lines.extend(self.truncation_line(hexdump_line_count(bytes)));
+            return (lines, vec![])
+        }
+
+        if let Some(summary) = &self.binary {
+            return (summary.clone(), vec![])
+        }
+
+        let shown = &self.diff[..self.diff.len().min(self.line_limit)];
+
+        // Untracked files are shown as they are, there is nothing
+        // to put side by side.
+        let (mut lines, line_numbers) = match self.file_state {
+            FileState::Untracked => (self.rendered.highlight_file(shown, &self.path), (1..=shown.len()).map(Some).collect()),
+            _ => self.rendered.render_with_line_numbers(shown, Some(&self.path), self.layout, window.width())
+        };
+
+        lines.extend(self.truncation_line(self.diff.len()));
+        (lines, line_numbers)
+    }
+
+    fn truncation_line(&self, total: usize) -> Option<Line> {
+        if total <= self.line_limit { return None }
+
+        Some(Line::from_string(
+            format!("... {} more lines (m: load more)", total - self.line_limit),
+            Some(vec![Style::Bold])
+        ))
+    }
+
+    fn render(&mut self, window: &mut Window) {
+        let (lines, line_numbers) = match self.file_state {
+            FileState::Untracked => self.body(window),
+            _ => {
+                let mut showing = vec![
+                    Part::new("Showing:", Some(vec![Style::Bold, Style::Underlined])),
+                    Part::plain(&format!(" {}", self.source.description()))
+                ];
+
+                if self.partially_staged {
+                    showing.push(Part::plain("  (i: switch between staged and unstaged)"));
+                }
+
+                let (body, body_line_numbers) = self.body(window);
+
+                let mut lines = vec![Line::new(showing), diff_options_header(&self.options)];
+                let mut line_numbers = vec![None; lines.len()];
+
+                lines.extend(body);
+                line_numbers.extend(body_line_numbers);
+                (lines, line_numbers)
+            }
+        };
+
+        self.line_numbers = line_numbers;
+        self.hunk_rows = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.value().starts_with("@@"))
+            .map(|(i, _)| i)
+            .collect();
+
+        window.set_lines(lines);
+    }
 }
 
 impl Component<DiffWindow> for DiffWindow {
     fn on_start(&mut self, window: &mut Window) {
         window.show_cursor(false);
+        self.load(window);
+    }
 
-        match self.file_state {
-            FileState::Untracked => {
-                // Assume the path is a file path
-                // Component above should parse directories into file paths.
-                let file = File::open(&self.path).expect("Could not find file");
-                let lines: Vec<String> = BufReader::new(file)
-                    .lines()
-                    .map_while(|l| l.ok())
-                    .collect();
-
-                window.set_lines(
-                    lines
-                        .iter()
-                        .map(|l| color_diff_line(l))
-                        .collect()
-                );
-            },
-            _ => {
-                window.set_lines(
-                    git::diff_file(&self.path)
-                        .iter()
-                        .map(|l| color_diff_line(l))
-                        .collect()
-                );
-            }
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        let Ok(loaded) = result.downcast::<(usize, LoadedDiff)>() else { return };
+        let (load, loaded) = *loaded;
+        if load != self.load { return }
+
+        self.partially_staged = loaded.partially_staged;
+        self.diff = loaded.diff;
+        self.binary = loaded.binary;
+        self.bytes = loaded.bytes;
+
+        if self.hexdump && self.bytes.is_none() {
+            self.bytes = Some(new_content(&self.path, self.source));
         }
+
+        self.render(window);
     }
 
     fn on_exit(&mut self, window: &mut Window) {
@@ -92,8 +321,22 @@ impl Component<DiffWindow> for DiffWindow {
 
         handlers.insert(4, DiffWindow::jump_screen_down);
         handlers.insert(21, DiffWindow::jump_screen_up);
+        handlers.insert(KEY_S_LOWER, DiffWindow::toggle_layout);
+        handlers.insert(KEY_I_LOWER, DiffWindow::toggle_source);
+        handlers.insert(KEY_X_LOWER, DiffWindow::toggle_hexdump);
+        handlers.insert(KEY_M_LOWER, DiffWindow::load_more);
+        handlers.insert(KEY_E_LOWER, DiffWindow::open_editor);
+        handlers.insert(KEY_T_LOWER, DiffWindow::stage_hunk);
+        handlers.insert(KEY_U_LOWER, DiffWindow::unstage_hunk);
 
         register_search_handlers(handlers);
+        register_diff_option_handlers(handlers);
+    }
+}
+
+impl DiffableComponent<DiffWindow> for DiffWindow {
+    fn diff_options(&mut self) -> &mut DiffOptions {
+        &mut self.options
     }
 }
 
@@ -106,3 +349,174 @@ impl SearchableComponent<DiffWindow> for DiffWindow {
         self.term = term;
     }
 }
+
+// Read on another thread, diffs of big files take a while.
+struct LoadedDiff {
+    partially_staged: bool,
+    diff: Vec<String>,
+    binary: Option<Vec<Line>>,
+    bytes: Option<Vec<u8>>
+}
+
+// Content of the file on the right side of the diff.
+fn new_content(path: &str, source: DiffSource) -> Vec<u8> {
+    match source {
+        DiffSource::Index => git::object_bytes(&format!(":{}", path)),
+        DiffSource::Worktree => fs::read(path).unwrap_or_default()
+    }
+}
+
+fn load_untracked(path: &str) -> LoadedDiff {
+    // Assume the path is a file path
+    // Component above should parse directories into file paths.
+    let bytes = fs::read(path).unwrap_or_default();
+
+    let (diff, binary) = if is_binary(&bytes) {
+        let mime = guess_mime(path, &bytes);
+        (vec![], Some(binary_summary(path, mime, None, Some(bytes.len() as u64))))
+    } else {
+        (String::from_utf8_lossy(&bytes).lines().map(str::to_owned).collect(), None)
+    };
+
+    LoadedDiff { partially_staged: false, diff, binary, bytes: Some(bytes) }
+}
+
+fn load_diff(path: &str, source: DiffSource, options: &DiffOptions) -> LoadedDiff {
+    let partially_staged = git::is_partially_staged(path);
+
+    let diff = match source {
+        DiffSource::Worktree => git::diff_file(path, options),
+        DiffSource::Index    => git::diff_staged_file(path, options)
+    };
+
+    if !diff.iter().any(|l| l.starts_with("Binary files ")) {
+        return LoadedDiff { partially_staged, diff, binary: None, bytes: None }
+    }
+
+    let index = format!(":{}", path);
+    let (old_size, new_size) = match source {
+        DiffSource::Worktree => (
+            git::object_size(&index),
+            fs::metadata(path).ok().map(|m| m.len())
+        ),
+        DiffSource::Index => (
+            git::object_size(&format!("HEAD:{}", path)),
+            git::object_size(&index)
+        )
+    };
+
+    let bytes = new_content(path, source);
+    let mime = guess_mime(path, &bytes);
+
+    LoadedDiff {
+        partially_staged,
+        diff,
+        binary: Some(binary_summary(path, mime, old_size, new_size)),
+        bytes: Some(bytes)
+    }
+}
+
+pub fn configured_line_limit() -> usize {
+    git::config_value("gitko.diffLineLimit")
+        .and_then(|v| v.parse().ok())
+        .filter(|l| *l > 0)
+        .unwrap_or(DEFAULT_LINE_LIMIT)
+}
+
+// The file header and the hunk with the given index of a single file
+// diff. Context lines are taken from the preimage when it is given.
+fn hunk_patch(diff: &[String], hunk: usize, preimage: Option<&[String]>) -> Option<String> {
+    let is_hunk_header = |l: &String| l.starts_with("@@");
+
+    let header_end = diff.iter().position(is_hunk_header)?;
+    let start = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| is_hunk_header(l))
+        .nth(hunk)?
+        .0;
+    let end = diff[start + 1..]
+        .iter()
+        .position(|l| is_hunk_header(l) || l.starts_with("diff "))
+        .map(|i| start + 1 + i)
+        .unwrap_or(diff.len());
+
+    let (mut old_line, _) = parse_hunk_header(&diff[start])?;
+    let mut hunk_lines = vec![diff[start].clone()];
+
+    // Empty lines of the file have a ' ' prefix in the hunk, only the
+    // end of the output is empty.
+    for line in diff[start + 1..end].iter().filter(|l| !l.is_empty()) {
+        if line.starts_with(' ') {
+            let original = preimage.and_then(|p| p.get(old_line - 1));
+            hunk_lines.push(original.map(|o| format!(" {}", o)).unwrap_or(line.clone()));
+            old_line += 1;
+        } else {
+            if line.starts_with('-') { old_line += 1; }
+            hunk_lines.push(line.clone());
+        }
+    }
+
+    let lines: Vec<String> = diff[..header_end]
+        .iter()
+        .cloned()
+        .chain(hunk_lines)
+        .collect();
+
+    Some(lines.join("\n") + "\n")
+}
+
+fn show_errors(window: &Window, errors: Vec<String>) {
+    let errors: Vec<String> = errors.into_iter().filter(|l| !l.is_empty()).collect();
+    if errors.is_empty() { return }
+
+    let height = errors.len() as i32 + 1;
+    Renderer::new(
+        &mut OutputWindow { output: errors },
+        ScreenSize { lines: height, cols: window.width() },
+        Position { x: 0, y: max_height() - height },
+        screen()
+    ).render();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hunk_patch;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn hunk_patch_keeps_the_file_header_and_one_hunk() {
+        let diff = lines("diff --git a/f b/f\nindex 1..2 100644\n--- a/f\n+++ b/f\n\
+                          @@ -1,2 +1,2 @@\n-a\n+b\n \n@@ -9 +9 @@\n-c\n+d\n");
+
+        assert_eq!(
+            hunk_patch(&diff, 1, None).as_deref(),
+            Some("diff --git a/f b/f\nindex 1..2 100644\n--- a/f\n+++ b/f\n@@ -9 +9 @@\n-c\n+d\n")
+        );
+        assert_eq!(
+            hunk_patch(&diff, 0, None).as_deref(),
+            Some("diff --git a/f b/f\nindex 1..2 100644\n--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n-a\n+b\n \n")
+        );
+    }
+
+    #[test]
+    fn hunk_patch_is_none_without_the_hunk() {
+        let diff = lines("diff --git a/f b/f\nBinary files a/f and b/f differ\n");
+
+        assert_eq!(hunk_patch(&diff, 0, None), None);
+    }
+
+    #[test]
+    fn hunk_patch_takes_the_context_from_the_preimage() {
+        let diff = lines("--- a/f\n+++ b/f\n@@ -2,3 +2,3 @@\n b  \n-c\n+C\n d\n");
+        let preimage = lines("a\nb\nc\nd");
+
+        assert_eq!(
+            hunk_patch(&diff, 0, Some(&preimage)).as_deref(),
+            Some("--- a/f\n+++ b/f\n@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n")
+        );
+    }
+}
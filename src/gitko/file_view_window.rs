@@ -0,0 +1,105 @@
+use crate::git;
+use crate::git::GrepTarget;
+use crate::editor::open_in_editor;
+use crate::gitko::diff_display::highlight_file;
+use crate::searchable::{SearchableComponent, register_search_handlers};
+use gitko_render::{Component, KeyHandlers, Line, Part, Style, TaskResult, Window};
+
+use gitko_common::ascii_table::KEY_E_LOWER;
+
+// Lines above the content of the file.
+const HEADER_LINES: usize = 2;
+
+// Read only view of a file in the worktree, the index or a revision.
+pub struct FileViewWindow {
+    path: String,
+    target: GrepTarget,
+    // Line to put the cursor on, starting at 1.
+    line: usize,
+    term: String
+}
+
+impl FileViewWindow {
+    pub fn new(path: &str, target: GrepTarget, line: usize) -> FileViewWindow {
+        FileViewWindow {
+            path: path.to_owned(),
+            target,
+            line,
+            term: String::new()
+        }
+    }
+
+    fn open_editor(&mut self, window: &mut Window) -> bool {
+        if self.target != GrepTarget::Worktree { return true }
+
+        if let Some(line) = window.cursor_line_index().checked_sub(HEADER_LINES) {
+            self.line = line + 1;
+        }
+
+        open_in_editor(&self.path, Some(self.line));
+
+        self.on_start(window);
+        true
+    }
+
+    fn header(&self) -> Vec<Line> {
+        vec![
+            Line::new(vec![
+                Part::new(&self.path, Some(vec![Style::Bold, Style::Underlined])),
+                Part::plain(&format!(" ({})", self.target.description()))
+            ]),
+            Line::empty()
+        ]
+    }
+}
+
+// Reading and highlighting large files takes a while, it is done
+// in the background.
+fn load_file(path: &str, target: &GrepTarget) -> Vec<Line> {
+    let content = git::file_content(path, target);
+    let width = content.len().to_string().len();
+
+    highlight_file(&content, path)
+        .into_iter()
+        .enumerate()
+        .map(|(number, mut line)| {
+            line.parts.insert(0, Part::painted(&format!("{:>width$} ", number + 1), (128, 128, 128), (0, 0, 0)));
+            line
+        })
+        .collect()
+}
+
+impl Component<FileViewWindow> for FileViewWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        window.set_lines(self.header());
+
+        let (path, target) = (self.path.clone(), self.target.clone());
+        window.spawn_task(&format!("Loading {}", self.path), move || load_file(&path, &target));
+    }
+
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        let Ok(content) = result.downcast::<Vec<Line>>() else { return };
+
+        let mut lines = self.header();
+        lines.extend(*content);
+
+        window.set_lines(lines);
+        window.move_to_line(HEADER_LINES + self.line.saturating_sub(1));
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<FileViewWindow>) {
+        handlers.insert(KEY_E_LOWER, FileViewWindow::open_editor);
+
+        register_search_handlers(handlers);
+    }
+}
+
+impl SearchableComponent<FileViewWindow> for FileViewWindow {
+    fn term(&self) -> String {
+        self.term.clone()
+    }
+
+    fn set_term(&mut self, term: String) {
+        self.term = term;
+    }
+}
@@ -0,0 +1,235 @@
+use crate::git;
+use crate::git::{GrepMatch, GrepTarget};
+use crate::screen;
+use crate::editor::open_in_editor;
+use crate::searchable::SearchableComponent;
+use crate::gitko::input_window::read_input;
+use crate::gitko::file_view_window::FileViewWindow;
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, TaskResult, Window};
+
+use gitko_common::ascii_table::{KEY_E_LOWER, KEY_I_LOWER, KEY_LF, KEY_P_LOWER, KEY_T_LOWER};
+
+const MATCH_COLOR: (u8, u8, u8) = (0, 0, 0);
+const MATCH_BACKGROUND: (u8, u8, u8) = (255, 200, 0);
+
+pub struct GrepWindow {
+    pattern: String,
+    target: GrepTarget,
+    ignore_case: bool,
+    matches: Vec<GrepMatch>,
+    // Index into matches of each rendered line, None for file headers.
+    line_matches: Vec<Option<usize>>,
+    // Counts the searches, results of an older search which arrive
+    // after a newer one started are dropped.
+    search: usize
+}
+
+impl GrepWindow {
+    pub fn new() -> GrepWindow {
+        GrepWindow {
+            pattern: String::new(),
+            target: GrepTarget::Worktree,
+            ignore_case: false,
+            matches: vec![],
+            line_matches: vec![],
+            search: 0
+        }
+    }
+
+    fn selected(&self, window: &Window) -> Option<&GrepMatch> {
+        self.line_matches
+            .get(window.cursor_line_index())
+            .copied()
+            .flatten()
+            .map(|i| &self.matches[i])
+    }
+
+    fn change_pattern(&mut self, window: &mut Window) -> bool {
+        let pattern = read_input(window, "Enter text to search for:");
+        if !pattern.is_empty() {
+            self.pattern = pattern;
+        }
+
+        self.on_start(window);
+        true
+    }
+
+    // Cycles between the worktree, the index and a revision
+    // which is asked for.
+    fn next_target(&mut self, window: &mut Window) -> bool {
+        self.target = match self.target {
+            GrepTarget::Worktree    => GrepTarget::Index,
+            GrepTarget::Index       => {
+                let revision = read_input(window, "Enter revision to search in:");
+                if revision.is_empty() { GrepTarget::Worktree } else { GrepTarget::Revision(revision) }
+            },
+            GrepTarget::Revision(_) => GrepTarget::Worktree
+        };
+
+        self.on_start(window);
+        true
+    }
+
+    fn toggle_ignore_case(&mut self, window: &mut Window) -> bool {
+        self.ignore_case = !self.ignore_case;
+        self.on_start(window);
+        true
+    }
+
+    fn open_viewer(&mut self, window: &mut Window) -> bool {
+        let Some(selected) = self.selected(window) else { return true };
+
+        let mut viewer = FileViewWindow::new(&selected.path, self.target.clone(), selected.line);
+        viewer.set_term(self.pattern.clone());
+
+        Renderer::new(
+            &mut viewer,
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
+
+        true
+    }
+
+    // Only the worktree has files which can be edited.
+    fn open_editor(&mut self, window: &mut Window) -> bool {
+        if self.target != GrepTarget::Worktree { return true }
+        let Some(selected) = self.selected(window) else { return true };
+
+        open_in_editor(&selected.path, Some(selected.line));
+
+        self.on_start(window);
+        true
+    }
+
+    fn render(&mut self, window: &mut Window) {
+        let files = self.matches
+            .iter()
+            .enumerate()
+            .filter(|(i, m)| *i == 0 || self.matches[i - 1].path != m.path)
+            .count();
+
+        let case = if self.ignore_case { ", ignoring case" } else { "" };
+        let count = if window.has_running_tasks() {
+            "searching...".to_owned()
+        } else {
+            format!("{} matches in {} files", self.matches.len(), files)
+        };
+
+        let mut lines = vec![
+            Line::new(vec![
+                Part::new("Grep:", Some(vec![Style::Bold, Style::Underlined])),
+                Part::plain(&format!(
+                    " '{}' in {}{}, {}",
+                    self.pattern,
+                    self.target.description(),
+                    case,
+                    count
+                ))
+            ]),
+            Line::plain("p: pattern, t: worktree/index/revision, i: ignore case, enter: view, e: edit"),
+            Line::empty()
+        ];
+        let mut line_matches = vec![None; lines.len()];
+
+        for (i, grep_match) in self.matches.iter().enumerate() {
+            if i == 0 || self.matches[i - 1].path != grep_match.path {
+                if i != 0 {
+                    lines.push(Line::empty());
+                    line_matches.push(None);
+                }
+
+                let count = self.matches.iter().filter(|m| m.path == grep_match.path).count();
+                lines.push(Line::from_string(
+                    format!("{} ({})", grep_match.path, count),
+                    Some(vec![Style::Bold, Style::Underlined])
+                ));
+                line_matches.push(None);
+            }
+
+            let mut parts = vec![Part::painted(&format!("{:>6}: ", grep_match.line), (128, 128, 128), (0, 0, 0))];
+            parts.extend(highlight_matches(&grep_match.text, &self.pattern, self.ignore_case));
+
+            lines.push(Line::new(parts));
+            line_matches.push(Some(i));
+        }
+
+        self.line_matches = line_matches;
+        window.set_lines(lines);
+    }
+}
+
+fn highlight_matches(text: &str, pattern: &str, ignore_case: bool) -> Vec<Part> {
+    if pattern.is_empty() { return vec![Part::plain(text)] }
+
+    // ASCII lowercasing keeps the byte offsets of both strings.
+    let (haystack, needle) = if ignore_case {
+        (text.to_ascii_lowercase(), pattern.to_ascii_lowercase())
+    } else {
+        (text.to_owned(), pattern.to_owned())
+    };
+
+    let mut parts = vec![];
+    let mut position = 0;
+
+    for (start, _) in haystack.match_indices(&needle) {
+        if start < position { continue }
+
+        if start > position {
+            parts.push(Part::plain(&text[position..start]));
+        }
+
+        let end = start + needle.len();
+        parts.push(Part::painted(&text[start..end], MATCH_COLOR, MATCH_BACKGROUND));
+        position = end;
+    }
+
+    if position < text.len() {
+        parts.push(Part::plain(&text[position..]));
+    }
+
+    parts
+}
+
+impl Component<GrepWindow> for GrepWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        if self.pattern.is_empty() {
+            self.pattern = read_input(window, "Enter text to search for:");
+        }
+
+        self.search += 1;
+
+        if self.pattern.is_empty() {
+            self.matches = vec![];
+        } else {
+            let (search, pattern, target, ignore_case) =
+                (self.search, self.pattern.clone(), self.target.clone(), self.ignore_case);
+
+            window.spawn_task(&format!("Searching for '{}'", self.pattern), move || {
+                (search, git::grep(&pattern, &target, ignore_case))
+            });
+        }
+
+        self.render(window);
+    }
+
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        let Ok(result) = result.downcast::<(usize, Vec<GrepMatch>)>() else { return };
+
+        let (search, matches) = *result;
+        if search == self.search {
+            self.matches = matches;
+        }
+
+        self.render(window);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<GrepWindow>) {
+        handlers.insert(KEY_LF, GrepWindow::open_viewer);
+        handlers.insert(KEY_E_LOWER, GrepWindow::open_editor);
+        handlers.insert(KEY_I_LOWER, GrepWindow::toggle_ignore_case);
+        handlers.insert(KEY_P_LOWER, GrepWindow::change_pattern);
+        handlers.insert(KEY_T_LOWER, GrepWindow::next_target);
+    }
+}
@@ -1,4 +1,6 @@
-use gitko_render::{Component, Window};
+use crate::{screen, max_width};
+use crate::gitko::text_window::TextWindow;
+use gitko_render::{Component, Position, Renderer, ScreenSize, Window};
 
 pub struct InputWindow {
     pub text: String
@@ -19,3 +21,25 @@ impl Component<InputWindow> for InputWindow {
         false
     }
 }
+
+// Asks for a line of text at the bottom of the window.
+pub fn read_input(window: &Window, message: &str) -> String {
+    Renderer::new(
+        &mut TextWindow {
+            lines: vec![message]
+        },
+        ScreenSize { lines: 5, cols: max_width() }, // TODO
+        Position { x: 0, y: window.height() - 2 },
+        screen()
+    ).draw();
+
+    let mut input_window = InputWindow::new();
+    Renderer::new(
+        &mut input_window,
+        ScreenSize { lines: 2, cols: max_width() },
+        Position { x: 0, y: window.height() - 1 },
+        screen()
+    ).render();
+
+    input_window.text
+}
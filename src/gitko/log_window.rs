@@ -1,25 +1,191 @@
+use std::cell::Cell;
+
 use crate::git;
 use crate::screen;
 use crate::max_height;
+use crate::gitko::input_window::read_input;
 use crate::gitko::output_window::OutputWindow;
+use crate::gitko::prompt_window::PromptWindow;
+use crate::gitko::command_output_window::run_with_output;
+use crate::gitko::compare_window::CompareWindow;
 use crate::gitko::commit_diff_window::CommitDiffWindow;
 use crate::gitko::detailed_commit_window::DetailedCommitWindow;
 use crate::searchable::{SearchableComponent, register_search_handlers};
-use gitko_render::{Component, KeyHandlers, Line,Renderer, ScreenSize, Window, Position, Part};
+use crate::undo::register_undo_handlers;
+use crate::file_watcher::{FileWatcher, FilesChanged, WatchScope};
+use gitko_render::{Component, KeyHandlers, Line,Renderer, ScreenSize, Window, Position, Part, Style, TaskResult};
+
+use gitko_common::ascii_table::{KEY_LF, KEY_M_LOWER, KEY_N_LOWER, KEY_N_UPPER, KEY_R_UPPER, KEY_D_LOWER,
+                                KEY_B_LOWER, KEY_G_LOWER, KEY_S_LOWER, KEY_X_LOWER, KEY_X_UPPER};
 
-use gitko_common::ascii_table::{KEY_LF, KEY_N_LOWER, KEY_N_UPPER, KEY_R_UPPER, KEY_D_LOWER};
+// The log of a big repository takes a while, it is read on another thread.
+struct Log {
+    bisect: Option<Line>,
+    commits: Vec<String>
+}
 
 pub struct LogWindow {
     term: String,
+    marked_commit: Option<String>,
+    log: Option<Log>,
+    // Counts the loads, to tell which one is the latest.
+    log_requests: usize,
+    watcher: Option<FileWatcher>
 }
 
 impl LogWindow {
     pub fn new() -> LogWindow {
-        LogWindow { term: "".to_owned() }
+        LogWindow { term: "".to_owned(), marked_commit: None, log: None, log_requests: 0, watcher: None }
+    }
+
+    // The result is shown by on_task_done, only the last load started
+    // is shown.
+    fn load_log(&mut self, window: &mut Window) {
+        self.log_requests += 1;
+
+        let request = self.log_requests;
+        window.spawn_task("Loading log", move || (request, read_log()));
+    }
+
+    fn log_lines(&self) -> Vec<Line> {
+        let Some(log) = &self.log else { return vec![] };
+        let mut lines: Vec<Line> = vec![];
+
+        if let Some(bisect) = &log.bisect {
+            lines.push(bisect.clone());
+        }
+
+        if let Some(marked) = &self.marked_commit {
+            lines.push(Line::from_string(
+                format!("Marked {} - press 'm' on another commit to compare.", marked),
+                Some(vec![Style::Bold])
+            ));
+        }
+
+        lines.extend(
+            log.commits
+                .iter()
+                .map(|l| map_line(l))
+        );
+
+        lines
+    }
+
+    // Marks the first side of a comparison, marking a second commit
+    // opens the comparison between the two.
+    fn mark_commit(&mut self, window: &mut Window) -> bool {
+        let line = window.cursor_line_value().trim().to_owned();
+        let Some(commit_hash) = parse_commit_hash(&line) else { return true };
+
+        match self.marked_commit.take() {
+            Some(marked) if marked != commit_hash => {
+                Renderer::new(
+                    &mut CompareWindow::new(&marked, commit_hash),
+                    ScreenSize::max(),
+                    Position::default(),
+                    screen()
+                ).render();
+            }
+            Some(_) => { }
+            None    => { self.marked_commit = Some(commit_hash.to_owned()); }
+        }
+
+        window.set_lines(self.log_lines());
+
+        true
+    }
+
+    fn bisect_good(&mut self, window: &mut Window) -> bool {
+        self.bisect_mark(window, "good")
+    }
+
+    fn bisect_bad(&mut self, window: &mut Window) -> bool {
+        self.bisect_mark(window, "bad")
+    }
+
+    fn bisect_skip(&mut self, window: &mut Window) -> bool {
+        self.bisect_mark(window, "skip")
+    }
+
+    // The first mark starts a bisect, which checks out commits, so a
+    // stray key asks before it does.
+    fn bisect_mark(&mut self, window: &mut Window, term: &str) -> bool {
+        let line = window.cursor_line_value().trim().to_owned();
+        let Some(commit_hash) = parse_commit_hash(&line) else { return true };
+
+        if !git::is_bisecting() {
+            let confirmed = Cell::new(false);
+
+            Renderer::new(
+                &mut PromptWindow::new(
+                    &format!("Start a bisect with {} marked as {}? y/n", commit_hash, term),
+                    || confirmed.set(true),
+                    || {}
+                ),
+                ScreenSize { lines: 1, cols: 0 },
+                Position { x: 0, y: window.height() - 1 },
+                screen()
+            ).render();
+
+            if !confirmed.get() {
+                window.set_lines(self.log_lines());
+                return true
+            }
+        }
+
+        let output = git::bisect(term, commit_hash);
+        self.show_first_bad_commit(&output);
+
+        self.on_start(window);
+
+        true
+    }
+
+    // Runs the command on every candidate with 'git bisect run', the
+    // output is shown while the command is still running and esc stops it.
+    fn bisect_run(&mut self, window: &mut Window) -> bool {
+        if !git::is_bisecting() { return true }
+
+        let command = read_input(window, "Enter command to test commits with (exit code 0 is good):");
+        if command.is_empty() {
+            window.set_lines(self.log_lines());
+            return true
+        }
+
+        let (output, _) = run_with_output("git bisect run", false, |on_update| git::bisect_run(&command, on_update));
+
+        if !output.interrupted {
+            self.show_first_bad_commit(&output.text());
+        }
+
+        self.on_start(window);
+
+        true
+    }
+
+    fn bisect_reset(&mut self, window: &mut Window) -> bool {
+        if git::is_bisecting() {
+            git::bisect_reset();
+        }
+
+        self.on_start(window);
+
+        true
+    }
+
+    fn show_first_bad_commit(&self, output: &[String]) {
+        let Some(commit_hash) = git::first_bad_commit(output) else { return };
+
+        Renderer::new(
+            &mut DetailedCommitWindow::new(&commit_hash),
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
     }
 
     fn get_commit_log(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
+        let line = window.cursor_line_value();
         let trimmed_line = line
             .trim_matches(|c| c == '|' || c == '\\' || c == '*' || c == ' ');
 
@@ -39,7 +205,7 @@ impl LogWindow {
     }
 
     fn open_detailed_log_window(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line().trim().to_owned();
+        let line = window.cursor_line_value().trim().to_owned();
         if let Some(commit_hash) = parse_commit_hash(&line) {
             Renderer::new(
                 &mut DetailedCommitWindow::new(commit_hash),
@@ -53,7 +219,7 @@ impl LogWindow {
     }
 
     fn open_reset_options(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line().trim().to_owned();
+        let line = window.cursor_line_value().trim().to_owned();
 
         if let Some(commit_hash) = parse_commit_hash(&line) {
             Renderer::new(
@@ -70,6 +236,36 @@ impl LogWindow {
     }
 }
 
+fn read_log() -> Log {
+    Log {
+        bisect: git::is_bisecting().then(bisect_header),
+        commits: git::log(None)
+    }
+}
+
+fn bisect_header() -> Line {
+    let keys = "g: good, b: bad, s: skip, x: run command, X: stop";
+
+    let status = match git::bisect_status() {
+        Some(status) if status.suspects <= 1 => {
+            format!("first bad commit is {:.7}", status.candidate)
+        }
+        Some(status) => format!(
+            "testing {:.7}, {} revisions left (roughly {} steps)",
+            status.candidate,
+            status.revisions_left,
+            status.steps_left
+        ),
+        None => "waiting for a good and a bad commit".to_owned()
+    };
+
+    Line::new(vec![
+        Part::new("Bisect:", Some(vec![Style::Bold, Style::Underlined])),
+        Part::painted(&format!(" {}", status), (255, 255, 0), (0, 0, 0)),
+        Part::plain(&format!("  ({})", keys))
+    ])
+}
+
 fn map_line(line: &str) -> Line {
     let mut parts: Vec<Part> = vec![];
 
@@ -83,15 +279,13 @@ fn map_line(line: &str) -> Line {
                 parts.push(Part::plain(&line[0..hash_start]));
 
                 const HASH_LENGTH: usize = 7;
-                parts.push(
-                    Part::painted(
-                        &line[hash_start..hash_start + HASH_LENGTH],
-                        (255, 255, 0),
-                        (0, 0, 0)
-                    )
-                );
+                let hash = &line[hash_start..hash_start + HASH_LENGTH];
+                parts.push(Part::painted(hash, (255, 255, 0), (0, 0, 0)));
 
                 parts.push(Part::plain(&line[hash_start + HASH_LENGTH..]));
+
+                // The cursor stays on the commit when new ones show up.
+                return Line::new(parts).with_key(hash)
             }
         }
         None => {
@@ -104,21 +298,46 @@ fn map_line(line: &str) -> Line {
 
 impl Component<LogWindow> for LogWindow {
     fn on_start(&mut self, window: &mut Window) {
-        window.set_lines(
-            git::log(None)
-                .iter()
-                .map(|l| map_line(l))
-                .collect()
-        );
+        // Rows have to match the lines for the cursor to stay on its
+        // commit when the log is loaded again.
+        window.set_wrap(false);
+
+        // Commits made, fetched or reset in another terminal.
+        if self.watcher.is_none() {
+            self.watcher = FileWatcher::new(WatchScope::GitDir, window.task_sender());
+        }
+
+        self.load_log(window);
+    }
+
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        if result.is::<FilesChanged>() {
+            self.load_log(window);
+            return
+        }
+
+        let Ok(loaded) = result.downcast::<(usize, Log)>() else { return };
+        let (request, log) = *loaded;
+        if request != self.log_requests { return }
+
+        self.log = Some(log);
+        window.set_lines(self.log_lines());
     }
 
     fn register_handlers(&self, handlers: &mut KeyHandlers<LogWindow>) {
         handlers.insert(KEY_D_LOWER, LogWindow::get_commit_log);
         handlers.insert(KEY_N_LOWER, LogWindow::next_search_result);
         handlers.insert(KEY_LF, LogWindow::open_detailed_log_window);
+        handlers.insert(KEY_M_LOWER, LogWindow::mark_commit);
         handlers.insert(KEY_N_UPPER, LogWindow::prev_search_result);
         handlers.insert(KEY_R_UPPER, LogWindow::open_reset_options);
+        handlers.insert(KEY_G_LOWER, LogWindow::bisect_good);
+        handlers.insert(KEY_B_LOWER, LogWindow::bisect_bad);
+        handlers.insert(KEY_S_LOWER, LogWindow::bisect_skip);
+        handlers.insert(KEY_X_LOWER, LogWindow::bisect_run);
+        handlers.insert(KEY_X_UPPER, LogWindow::bisect_reset);
         register_search_handlers(handlers);
+        register_undo_handlers(handlers);
     }
 }
 
@@ -3,51 +3,79 @@ use std::path::{Path, PathBuf};
 use std::fs::{read_dir, remove_file};
 
 use crate::git;
-use crate::screen;
-use crate::git::{parse_file_state, FileState};
+use crate::{screen, max_height, max_width};
+use crate::git::{parse_file_state, FileState, Submodule, SubmoduleState};
 use crate::gitko::log_window::LogWindow;
-use crate::gitko::diff_window::DiffWindow;
+use crate::gitko::diff_window::{DiffWindow, DiffSource};
+use crate::gitko::diff_preview_window::{DiffPreviewWindow, PreviewFile, ScrollPreview};
+use crate::gitko::blame_window::BlameWindow;
 use crate::gitko::branch_window::BranchWindow;
+use crate::gitko::reflog_window::ReflogWindow;
+use crate::gitko::worktree_window::WorktreeWindow;
+use crate::gitko::grep_window::GrepWindow;
+use crate::editor::open_in_editor;
+use crate::file_watcher::{FileWatcher, FilesChanged, WatchScope};
 use crate::gitko::command_window::CommandWindow;
 use crate::gitko::prompt_window::PromptWindow;
 use crate::gitko::push_options_window::PushOptionsWindow;
+use crate::gitko::submodule_options_window::SubmoduleOptionsWindow;
 use crate::gitko::commit_options_window::CommitOptionsWindow;
+use crate::gitko::command_output_window::run_with_output;
 use crate::searchable::{SearchableComponent, register_search_handlers};
-use gitko_render::{Line, Renderer, KeyHandlers, Component, ScreenSize, Window, Position, Part, Style};
+use crate::undo::register_undo_handlers;
+use gitko_render::{Line, Layout, LayoutRenderer, Renderer, KeyHandlers, Component, ScreenSize, Window, Position, Part, Style,
+                   TaskResult};
 
-use gitko_common::ascii_table::{KEY_B_LOWER, KEY_COLON, KEY_C_LOWER, KEY_C_UPPER, KEY_D_LOWER, KEY_LF,
-                                KEY_L_LOWER, KEY_O_UPPER, KEY_P_UPPER, KEY_R_UPPER, KEY_T_LOWER, KEY_U_LOWER};
+use gitko_common::ascii_table::{KEY_B_LOWER, KEY_B_UPPER, KEY_COLON, KEY_C_LOWER, KEY_C_UPPER, KEY_D_LOWER, KEY_LF,
+                                KEY_L_LOWER, KEY_O_UPPER, KEY_P_UPPER, KEY_R_LOWER, KEY_R_UPPER, KEY_T_LOWER, KEY_U_LOWER,
+                                KEY_W_LOWER, KEY_G_LOWER, KEY_E_LOWER, KEY_F_LOWER, KEY_F_UPPER, KEY_J_UPPER,
+                                KEY_K_UPPER};
 
 const SECTION_UNTRACKED_MODIFIED: &str = "Untracked (modified) files";
 const SECTION_UNTRACKED: &str = "Untracked files";
 const SECTION_MODIFIED: &str = "Modified files";
 const SECTION_STAGED: &str = "Staged files";
 const SECTION_DELETED: &str = "Deleted files";
+const SECTION_SUBMODULES: &str = "Submodules";
 
-const SECTION_NAMES: [&str; 5] = [
+const SECTION_NAMES: [&str; 6] = [
     SECTION_UNTRACKED_MODIFIED,
     SECTION_UNTRACKED,
     SECTION_MODIFIED,
     SECTION_STAGED,
     SECTION_DELETED,
+    SECTION_SUBMODULES,
 ];
 
+// Narrower terminals only show the status, the diff would not fit
+// next to it.
+const MIN_PREVIEW_WIDTH: i32 = 100;
 
 pub struct MainWindow {
     term: String,
-    expanded_sections: Vec<String>
+    expanded_sections: Vec<String>,
+    status: Option<Status>,
+    // Counts the loads, to tell which one is the latest.
+    status_requests: usize,
+    watcher: Option<FileWatcher>,
+    // Last file sent to the preview pane.
+    previewed: Option<PreviewFile>
 }
 
 impl MainWindow {
     pub fn new() -> MainWindow {
         MainWindow {
             term: String::new(),
-            expanded_sections: vec![]
+            expanded_sections: vec![],
+            status: None,
+            status_requests: 0,
+            watcher: None,
+            previewed: None
         }
     }
 
     fn on_press_enter(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
+        let line = window.cursor_line_value();
 
         let mut selected_section = None;
         for section in SECTION_NAMES.iter() {
@@ -63,6 +91,13 @@ impl MainWindow {
                    Some(pos) => { self.expanded_sections.remove(pos); }
                    None      => { self.expanded_sections.push(section.to_string()); }
                }
+
+               // Nothing changed in the repository, only what is shown.
+               self.show_status(window);
+               return true
+           }
+           None if self.cursor_section(window) == Some(SECTION_SUBMODULES) => {
+               self.open_submodule_options(window);
            }
            None => { self.diff_file(window); }
         }
@@ -73,16 +108,99 @@ impl MainWindow {
     }
 
     fn diff_file(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
-        if line.is_empty() { return true }
-        if line.len() < 3  { return true }
+        let Some(file) = self.cursor_file(window) else { return true };
+
+        Renderer::new(
+            &mut DiffWindow::new(&file.path, file.file_state, file.source),
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
+
+        true
+    }
+
+    fn cursor_file(&self, window: &Window) -> Option<PreviewFile> {
+        let line = window.cursor_line_value();
+        if line.len() < 3 { return None }
+
+        let file_state = parse_file_state(&line);
+        if matches!(file_state, FileState::Unknown) { return None }
+
+        // Partially staged files are listed in both sections, the
+        // section decides which of the changes are shown.
+        let source = match self.cursor_section(window) {
+            Some(SECTION_SUBMODULES) => return None,
+            Some(SECTION_STAGED)     => DiffSource::Index,
+            _                        => DiffSource::Worktree
+        };
+
+        Some(PreviewFile { path: line[3..].trim().to_owned(), file_state, source })
+    }
+
+    // Tells the preview pane about the file under the cursor, only when
+    // it is another one unless `reload` is set, e.g. after it changed.
+    fn update_preview(&mut self, window: &mut Window, reload: bool) {
+        let file = self.cursor_file(window);
+        if !reload && file == self.previewed { return }
+
+        self.previewed = file.clone();
+        window.send_to_panes(file);
+    }
+
+    fn scroll_preview_down(&mut self, window: &mut Window) -> bool {
+        window.send_to_panes(ScrollPreview::Down);
+        true
+    }
+
+    fn scroll_preview_up(&mut self, window: &mut Window) -> bool {
+        window.send_to_panes(ScrollPreview::Up);
+        true
+    }
+
+    fn cursor_section(&self, window: &Window) -> Option<&'static str> {
+        let cursor = window.cursor_line_index();
+
+        window.lines
+            .iter()
+            .take(cursor + 1)
+            .rev()
+            .map(|l| l.value())
+            .find_map(|l| SECTION_NAMES.iter().find(|s| l.starts_with(*s)).copied())
+    }
+
+    fn open_submodule_options(&mut self, window: &mut Window) {
+        let line = window.cursor_line_value();
+        let line = line.trim_end();
+
+        let submodules = self.status.as_ref().map(|s| s.submodules.as_slice()).unwrap_or_default();
+        let Some(submodule) = submodules.iter().find(|s| submodule_line(s).value() == line) else { return };
+        let path = submodule.path.clone();
+        let initialized = submodule.checked_out.is_some();
+
+        let mut options = SubmoduleOptionsWindow::new(&path);
+        Renderer::new(
+            &mut options,
+            ScreenSize { lines: 4, cols: max_width() },
+            Position { x: 0, y: max_height() - 4 },
+            screen()
+        ).render();
+
+        if options.open_requested && initialized {
+            open_nested_session(&path);
+        }
+    }
+
+    fn open_blame_window(&mut self, window: &mut Window) -> bool {
+        let line = window.cursor_line_value();
+        if line.len() < 3 { return true }
 
         let file_state = parse_file_state(&line);
-        if matches!(file_state, FileState::Unknown) { return true }
+        if matches!(file_state, FileState::Unknown | FileState::Untracked) { return true }
 
         Renderer::new(
-            &mut DiffWindow::new(line[3..].trim(), file_state),
-            ScreenSize { lines: window.height(), cols: window.width() },
+            &mut BlameWindow::new(line[3..].trim(), None),
+            ScreenSize::max(),
             Position::default(),
             screen()
         ).render();
@@ -92,7 +210,7 @@ impl MainWindow {
 
     fn open_branch_window(&mut self, window: &mut Window) -> bool {
         Renderer::new(
-            &mut BranchWindow{},
+            &mut BranchWindow::new(),
             ScreenSize::max(),
             Position::default(),
             screen()
@@ -116,11 +234,52 @@ impl MainWindow {
         true
     }
 
+    fn open_reflog_window(&mut self, window: &mut Window) -> bool {
+        Renderer::new(
+            &mut ReflogWindow::new(),
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
+
+        self.on_start(window);
+
+        true
+    }
+
+    // Switching to another worktree changes the working directory,
+    // the status is reloaded for the new worktree afterwards.
+    fn open_worktree_window(&mut self, window: &mut Window) -> bool {
+        Renderer::new(
+            &mut WorktreeWindow::new(),
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
+
+        self.on_start(window);
+
+        true
+    }
+
+    fn open_grep_window(&mut self, window: &mut Window) -> bool {
+        Renderer::new(
+            &mut GrepWindow::new(),
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
+
+        self.on_start(window);
+
+        true
+    }
+
     fn open_command_window(&mut self, window: &mut Window) -> bool {
         Renderer::new(
             &mut CommandWindow{},
-            ScreenSize { lines: 2, cols: window.width() },
-            Position { x: 0, y: window.height() - 2 },
+            ScreenSize { lines: 2, cols: max_width() },
+            Position { x: 0, y: max_height() - 2 },
             screen()
         ).render();
 
@@ -129,6 +288,21 @@ impl MainWindow {
         true
     }
 
+    fn open_file_in_editor(&mut self, window: &mut Window) -> bool {
+        let line = window.cursor_line_value();
+        if line.len() < 3 { return true }
+
+        let path = line[3..].trim();
+        if !Path::new(path).is_file() { return true }
+
+        open_in_editor(path, None);
+
+        self.on_start(window);
+
+        true
+    }
+
+    #[allow(clippy::zombie_processes)]
     fn open_in_file_manager(&mut self, window: &mut Window) -> bool {
         let command = if cfg!(unix) {
             "xdg-open"
@@ -138,7 +312,7 @@ impl MainWindow {
             panic!("Platform not supported.");
         };
 
-        let cursor_line = window.get_cursor_line();
+        let cursor_line = window.cursor_line_value();
         if cursor_line.len() < 3 {
             return true
         }
@@ -164,7 +338,7 @@ impl MainWindow {
     }
 
     fn delete_untracked_file(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
+        let line = window.cursor_line_value();
         if line.is_empty() { return true }
 
         let file_state = parse_file_state(&line);
@@ -176,7 +350,7 @@ impl MainWindow {
                                   || { remove_file(file).unwrap(); },
                                   || {}),
                 ScreenSize { lines: 1, cols: 0 },
-                Position { x: 0, y: window.height() - 1 },
+                Position { x: 0, y: max_height() - 1 },
                 screen()
             ).render();
         }
@@ -187,7 +361,7 @@ impl MainWindow {
     }
 
     fn git_checkout_file(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
+        let line = window.cursor_line_value();
         if line.is_empty() { return true }
 
         let file_state = parse_file_state(&line);
@@ -199,7 +373,7 @@ impl MainWindow {
                                   || { git::checkout_file(file); },
                                   || {}),
                 ScreenSize { lines: 1, cols: 0 },
-                Position { x: 0, y: window.height() - 1 },
+                Position { x: 0, y: max_height() - 1 },
                 screen()
             ).render();
         }
@@ -212,7 +386,7 @@ impl MainWindow {
     fn git_add_file(&mut self, window: &mut Window) -> bool {
         // TODO: add parse git status that returns file state
         // and file path?
-        let line = window.get_cursor_line();
+        let line = window.cursor_line_value();
         if line.is_empty() { return true }
 
         if git::is_file_modified(&line) {
@@ -225,7 +399,7 @@ impl MainWindow {
     }
 
     fn git_unstage_file(&mut self, window: &mut Window) -> bool {
-        let line = window.get_cursor_line();
+        let line = window.cursor_line_value();
 
         if line.is_empty()             { return true }
         if line.len() < 3              { return true }
@@ -242,8 +416,8 @@ impl MainWindow {
     fn git_commit_options(&mut self, window: &mut Window) -> bool {
         Renderer::new(
             &mut CommitOptionsWindow{},
-            ScreenSize { lines: 2, cols: window.width() },
-            Position { x: 0, y: window.height() - 2 },
+            ScreenSize { lines: 2, cols: max_width() },
+            Position { x: 0, y: max_height() - 2 },
             screen()
         ).render();
 
@@ -254,8 +428,8 @@ impl MainWindow {
     fn git_push_options(&mut self, window: &mut Window) -> bool {
         Renderer::new(
             &mut PushOptionsWindow{},
-            ScreenSize { lines: 2, cols: window.width() },
-            Position { x: 0, y: window.height() - 2 },
+            ScreenSize { lines: 2, cols: max_width() },
+            Position { x: 0, y: max_height() - 2 },
             screen()
         ).render();
 
@@ -263,127 +437,111 @@ impl MainWindow {
         true
     }
 
-    fn refresh(&mut self, window: &mut Window) -> bool {
+    fn git_fetch(&mut self, window: &mut Window) -> bool {
+        run_with_output("git fetch", false, |on_update| git::fetch(on_update));
+
         self.on_start(window);
         true
     }
-}
 
-fn get_dir_file_paths(path: &Path) -> Vec<String> {
-    let mut paths = vec![];
+    fn git_pull(&mut self, window: &mut Window) -> bool {
+        run_with_output("git pull", false, |on_update| git::pull(on_update));
 
-    let Ok(metadata) = path.metadata() else { return paths };
+        self.on_start(window);
+        true
+    }
 
-    if metadata.is_file() {
-        paths.push(
-            path.to_str()
-                .unwrap()
-                .to_string()
-        );
-        return paths
+    fn refresh(&mut self, window: &mut Window) -> bool {
+        self.on_start(window);
+        true
     }
 
-    if metadata.is_dir() {
-        let Ok(dir_paths) = read_dir(path) else  { return paths };
+    // Watches the repository gitko currently runs in, which changes
+    // after switching worktrees.
+    fn watch_repository(&mut self, window: &mut Window) {
+        let root = git::toplevel();
+        let watched = self.watcher.as_ref().map(|w| &w.root);
+        if watched.is_some() && watched == root.as_ref() { return }
 
-        for path in dir_paths.flatten() {
-            let Ok(meta) = path.metadata() else { return paths };
+        self.watcher = FileWatcher::new(WatchScope::Worktree, window.task_sender());
+    }
 
-            if meta.is_dir() {
-                paths.append(&mut get_dir_file_paths(path.path().as_path()));
-            } else if meta.is_file() {
-                paths.push(
-                    path.path()
-                        .to_str()
-                        .unwrap()
-                        .to_string()
-                );
-            }
-        }
+    // Loads the status on another thread, the result is shown by
+    // on_task_done. Only the last one started is shown, an older one
+    // may finish later.
+    fn load_status(&mut self, window: &mut Window) {
+        self.status_requests += 1;
+
+        let request = self.status_requests;
+        window.spawn_task("Loading status", move || (request, read_status()));
     }
 
-    paths
-}
+    fn show_status(&mut self, window: &mut Window) {
+        let status = self.status_lines();
+        window.set_lines(status);
 
-impl Component<MainWindow> for MainWindow {
-    fn on_start(&mut self, window: &mut Window) {
-        let git_status: Vec<String> = git::status();
+        self.update_preview(window, true);
+    }
 
-        let untracked: Vec<String> = git_status
-            .iter()
-            .filter(|c| c.starts_with("??") || c.starts_with("AM"))
-            .cloned()
-            .collect();
+    fn status_lines(&self) -> Vec<Line> {
+        let Some(loaded) = &self.status else { return vec![] };
+        let git_status = &loaded.entries;
 
         let mut added: Vec<Line> = vec![];
         let mut added_modified: Vec<Line> = vec![];
 
-        for u in &untracked {
-            let untracked_path = &u[3..];
-
-            let modified = u.chars().nth(1).unwrap() == 'M';
-
-            let paths = get_dir_file_paths(Path::new(untracked_path));
-            let unignored_paths = paths
-                .iter()
-                .filter(|p| !git::is_ignored(Path::new(p)));
+        for (path, modified) in &loaded.untracked {
+            let line = Line::plain(&format!("?? {}", &path));
 
-            for path in unignored_paths {
-                let line = Line::plain(&format!("?? {}", &path));
-
-                if modified {
-                    added_modified.push(line);
-                } else {
-                    added.push(line);
-                }
+            if *modified {
+                added_modified.push(line.with_key(&file_key(SECTION_UNTRACKED_MODIFIED, path)));
+            } else {
+                added.push(line.with_key(&file_key(SECTION_UNTRACKED, path)));
             }
         }
 
         let mut deleted: Vec<Line> = git_status
             .iter()
             .filter(|c| c.starts_with(" D"))
-            .map(|c| Line::plain(c))
+            .map(|c| Line::plain(c).with_key(&file_key(SECTION_DELETED, &c[3..])))
             .collect();
 
         let mut unstaged: Vec<Line> = git_status
             .iter()
             .filter(|c| c.starts_with(" M") || c.starts_with("MM"))
-            .map(|c| Line::plain(c))
+            .map(|c| Line::plain(c).with_key(&file_key(SECTION_MODIFIED, &c[3..])))
             .collect();
 
         let mut staged: Vec<Line> = git_status
             .iter()
             .filter(|c| c.starts_with('M') || c.starts_with('A') || c.starts_with('D'))
-            .map(|c| Line::plain(c))
+            .map(|c| Line::plain(c).with_key(&file_key(SECTION_STAGED, &c[3..])))
             .collect();
 
         let mut status: Vec<Line> = vec![
             Line::new(vec![
                 Part::new("Head:", Some(vec![Style::Bold, Style::Underlined])),
                 Part::painted(
-                    &git::head_branch(),
+                    &loaded.head_branch,
                     (0, 255, 255),
                     (0, 0, 0)
                 ),
                 Part::plain(" "),
-                Part::plain(&git::last_commit())
+                Part::plain(&loaded.last_commit)
             ])
         ];
 
-        let origin_hash = git::last_origin_commit_hash();
-        let local_hash = git::last_commit_hash();
-
-        if origin_hash != local_hash { // if HEAD different from origin HEAD
+        if let Some((origin_branch, origin_commit)) = &loaded.origin { // if HEAD different from origin HEAD
             status.push(
                  Line::new(vec![
                      Part::new("Origin ", Some(vec![Style::Bold, Style::Underlined])),
                      Part::painted(
-                         &git::origin_head_branch(),
+                         origin_branch,
                          (255, 0, 0),
                          (0, 0, 0)
                      ),
                      Part::plain(" "),
-                     Part::plain(&git::last_origin_commit())
+                     Part::plain(origin_commit)
                  ])
             );
         }
@@ -397,7 +555,7 @@ impl Component<MainWindow> for MainWindow {
                         &format!("Untracked files: ({})", added.len()),
                         Some(vec![Style::Bold, Style::Underlined])
                     )
-                ])
+                ]).with_key(SECTION_UNTRACKED)
             );
 
             if self.expanded_sections.contains(&SECTION_UNTRACKED.to_string()) {
@@ -414,7 +572,7 @@ impl Component<MainWindow> for MainWindow {
                         &format!("Untracked (modified) files: ({})", added_modified.len()),
                         Some(vec![Style::Bold, Style::Underlined])
                     )]
-                )
+                ).with_key(SECTION_UNTRACKED_MODIFIED)
             );
 
             if self.expanded_sections.contains(&SECTION_UNTRACKED_MODIFIED.to_string()) {
@@ -427,8 +585,8 @@ impl Component<MainWindow> for MainWindow {
         if !deleted.is_empty() {
             status.push(Line::from_str(
                 "Deleted files:",
-                Some(vec![Style::Bold, Style::Underlined]))
-            );
+                Some(vec![Style::Bold, Style::Underlined])
+            ).with_key(SECTION_DELETED));
 
             if self.expanded_sections.contains(&SECTION_DELETED.to_string()) {
                 status.append(&mut deleted);
@@ -441,7 +599,7 @@ impl Component<MainWindow> for MainWindow {
             Line::from_str(
                 &format!("Modified files: ({})", unstaged.len()),
                 Some(vec![Style::Bold, Style::Underlined])
-            )
+            ).with_key(SECTION_MODIFIED)
         );
 
         if !unstaged.is_empty() && self.expanded_sections.contains(&SECTION_MODIFIED.to_string()) {
@@ -458,23 +616,234 @@ impl Component<MainWindow> for MainWindow {
                         &format!("Staged files: ({})", staged.len()),
                         Some(vec![Style::Bold, Style::Underlined])
                     )
-                ])
+                ]).with_key(SECTION_STAGED)
             );
             status.append(&mut staged);
         }
 
+        if !loaded.submodules.is_empty() {
+            status.push(Line::empty());
+            status.push(
+                Line::from_str(
+                    &format!("Submodules: ({})", loaded.submodules.len()),
+                    Some(vec![Style::Bold, Style::Underlined])
+                ).with_key(SECTION_SUBMODULES)
+            );
+            status.extend(
+                loaded.submodules
+                    .iter()
+                    .map(|s| submodule_line(s).with_key(&file_key(SECTION_SUBMODULES, &s.path)))
+            );
+        }
+
         if status.is_empty() {
             status.push(Line::plain("No changes found."));
         }
 
         status.push(Line::empty());
 
-        window.set_lines(status);
+        status
+    }
+}
+
+// Files are keyed by section too, a partially staged file is listed
+// both as modified and as staged.
+fn file_key(section: &str, path: &str) -> String {
+    format!("{}:{}", section, path)
+}
+
+fn submodule_line(submodule: &Submodule) -> Line {
+    let short = |hash: &str| hash.chars().take(7).collect::<String>();
+
+    let (state, color) = match submodule.state {
+        SubmoduleState::Uninitialized => ("not initialized", (128, 128, 128)),
+        SubmoduleState::InSync        => ("in sync", (0, 255, 0)),
+        SubmoduleState::CommitChanged => ("checked out commit differs", (255, 255, 0)),
+        SubmoduleState::Conflicted    => ("conflicted", (255, 0, 0))
+    };
+
+    let checked_out = submodule.checked_out
+        .as_deref()
+        .map(short)
+        .unwrap_or_else(|| "-------".to_owned());
+
+    let mut parts = vec![
+        Part::plain(&format!("{}  {} (recorded {})  ", submodule.path, checked_out, short(&submodule.recorded))),
+        Part::painted(state, color, (0, 0, 0))
+    ];
+
+    if submodule.dirty {
+        parts.push(Part::painted(", dirty", (255, 0, 0), (0, 0, 0)));
+    }
+
+    Line::new(parts)
+}
+
+// Runs a complete gitko session inside the submodule, returning to
+// this repository when it is closed.
+fn open_nested_session(path: &str) {
+    let Ok(previous) = std::env::current_dir() else { return };
+    if std::env::set_current_dir(path).is_err() { return }
+
+    render_main_window();
+
+    let _ = std::env::set_current_dir(previous);
+}
+
+// Shows the status with the diff of the file under the cursor next to
+// it. Narrow terminals and 'git config gitko.diffPreview false' only
+// show the status.
+pub fn render_main_window() {
+    let mut main_window = MainWindow::new();
+
+    if max_width() < MIN_PREVIEW_WIDTH || !git::config_flag_or("gitko.diffPreview", true) {
+        Renderer::new(
+            &mut main_window,
+            ScreenSize::max(),
+            Position::default(),
+            screen()
+        ).render();
+
+        return
+    }
+
+    let mut preview = DiffPreviewWindow::new();
+
+    LayoutRenderer::new(
+        Layout::horizontal(vec![
+            (2, Layout::pane("Status", &mut main_window)),
+            (3, Layout::pane("Diff", &mut preview))
+        ]),
+        ScreenSize { lines: max_height(), cols: max_width() },
+        Position::default(),
+        screen()
+    ).render();
+}
+
+// What the status is made of. Reading it runs git a few times and
+// walks untracked directories, which takes a while in big repositories.
+struct Status {
+    head_branch: String,
+    last_commit: String,
+    // Branch and last commit of origin, when HEAD differs from it.
+    origin: Option<(String, String)>,
+    entries: Vec<String>,
+    // Files in untracked entries which are not ignored, with whether
+    // the entry is 'AM'.
+    untracked: Vec<(String, bool)>,
+    submodules: Vec<Submodule>
+}
+
+fn read_status() -> Status {
+    let submodules = git::submodules();
+
+    // Submodules are listed in a section of their own. Status quotes
+    // paths with spaces, submodule status does not.
+    let entries: Vec<String> = git::status()
+        .into_iter()
+        .filter(|c| {
+            let path = c.get(3..).unwrap_or_default().trim_matches('"');
+            !submodules.iter().any(|s| s.path == path)
+        })
+        .collect();
+
+    let mut untracked = vec![];
+
+    for u in entries.iter().filter(|c| c.starts_with("??") || c.starts_with("AM")) {
+        let untracked_path = &u[3..];
+
+        let modified = u.chars().nth(1).unwrap() == 'M';
+
+        let paths = get_dir_file_paths(Path::new(untracked_path));
+        let unignored_paths = paths
+            .into_iter()
+            .filter(|p| !git::is_ignored(Path::new(p)));
+
+        untracked.extend(unignored_paths.map(|p| (p, modified)));
+    }
+
+    let origin = (git::last_origin_commit_hash() != git::last_commit_hash())
+        .then(|| (git::origin_head_branch(), git::last_origin_commit()));
+
+    Status {
+        head_branch: git::head_branch(),
+        last_commit: git::last_commit(),
+        origin,
+        entries,
+        untracked,
+        submodules
+    }
+}
+
+fn get_dir_file_paths(path: &Path) -> Vec<String> {
+    let mut paths = vec![];
+
+    let Ok(metadata) = path.metadata() else { return paths };
+
+    if metadata.is_file() {
+        paths.push(
+            path.to_str()
+                .unwrap()
+                .to_string()
+        );
+        return paths
+    }
+
+    if metadata.is_dir() {
+        let Ok(dir_paths) = read_dir(path) else  { return paths };
+
+        for path in dir_paths.flatten() {
+            let Ok(meta) = path.metadata() else { return paths };
+
+            if meta.is_dir() {
+                paths.append(&mut get_dir_file_paths(path.path().as_path()));
+            } else if meta.is_file() {
+                paths.push(
+                    path.path()
+                        .to_str()
+                        .unwrap()
+                        .to_string()
+                );
+            }
+        }
+    }
+
+    paths
+}
+
+impl Component<MainWindow> for MainWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        window.set_wrap(false);
+        self.watch_repository(window);
+
+        self.load_status(window);
+    }
+
+    fn on_render(&mut self, window: &mut Window) -> bool {
+        self.update_preview(window, false);
+        true
+    }
+
+    // Files changed outside of gitko, e.g. in an editor or another
+    // terminal.
+    fn on_task_done(&mut self, window: &mut Window, result: TaskResult) {
+        if result.is::<FilesChanged>() {
+            self.load_status(window);
+            return
+        }
+
+        let Ok(loaded) = result.downcast::<(usize, Status)>() else { return };
+        let (request, status) = *loaded;
+        if request != self.status_requests { return }
+
+        self.status = Some(status);
+        self.show_status(window);
     }
 
     fn register_handlers(&self, handlers: &mut KeyHandlers<MainWindow>) {
         handlers.insert(KEY_LF, MainWindow::on_press_enter);
         handlers.insert(KEY_B_LOWER, MainWindow::open_branch_window);
+        handlers.insert(KEY_B_UPPER, MainWindow::open_blame_window);
         handlers.insert(KEY_C_LOWER, MainWindow::git_checkout_file);
         handlers.insert(KEY_D_LOWER, MainWindow::delete_untracked_file);
         handlers.insert(KEY_L_LOWER, MainWindow::open_log_window);
@@ -484,9 +853,18 @@ impl Component<MainWindow> for MainWindow {
         handlers.insert(KEY_COLON, MainWindow::open_command_window);
         handlers.insert(KEY_C_UPPER, MainWindow::git_commit_options);
         handlers.insert(KEY_P_UPPER, MainWindow::git_push_options);
+        handlers.insert(KEY_R_LOWER, MainWindow::open_reflog_window);
         handlers.insert(KEY_R_UPPER, MainWindow::refresh);
+        handlers.insert(KEY_W_LOWER, MainWindow::open_worktree_window);
+        handlers.insert(KEY_G_LOWER, MainWindow::open_grep_window);
+        handlers.insert(KEY_E_LOWER, MainWindow::open_file_in_editor);
+        handlers.insert(KEY_F_LOWER, MainWindow::git_fetch);
+        handlers.insert(KEY_F_UPPER, MainWindow::git_pull);
+        handlers.insert(KEY_J_UPPER, MainWindow::scroll_preview_down);
+        handlers.insert(KEY_K_UPPER, MainWindow::scroll_preview_up);
 
         register_search_handlers(handlers);
+        register_undo_handlers(handlers);
     }
 }
 
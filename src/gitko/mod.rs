@@ -1,14 +1,27 @@
 pub mod log_window;
 pub mod diff_window;
+pub mod diff_preview_window;
+pub mod grep_window;
+pub mod file_view_window;
 pub mod main_window;
+pub mod blame_window;
 pub mod text_window;
 pub mod diff_display;
+pub mod binary_display;
+pub mod syntax_highlight;
 pub mod input_window;
 pub mod output_window;
+pub mod command_output_window;
 pub mod branch_window;
+pub mod reflog_window;
+pub mod worktree_window;
 pub mod prompt_window;
 pub mod command_window;
+pub mod compare_window;
 pub mod commit_diff_window;
 pub mod push_options_window;
+pub mod submodule_options_window;
 pub mod commit_options_window;
+pub mod commit_editor_window;
+pub mod commit_message;
 pub mod detailed_commit_window;
@@ -1,8 +1,6 @@
-use crate::screen;
 use crate::git;
-use crate::max_height;
-use crate::gitko::output_window::OutputWindow;
-use gitko_render::{Renderer, Line, KeyHandlers, Component, Window, ScreenSize, Position};
+use crate::gitko::command_output_window::run_with_output;
+use gitko_render::{Line, KeyHandlers, Component, Window};
 
 use gitko_common::ascii_table::{KEY_LF};
 
@@ -12,24 +10,23 @@ impl PushOptionsWindow {
     fn git_push(&mut self, window: &mut Window) -> bool {
         let line = window.get_cursor_line().trim().to_owned();
 
-        let args = if line.is_empty() {
-            None
+        let mut args = if line.is_empty() {
+            vec![]
         } else {
-            Some(vec![line.as_str()])
+            vec![line.as_str()]
         };
 
         window.clear();
 
-        let output = git::push(args);
-        if output.is_empty() { return false }
+        loop {
+            let (_, retry) = run_with_output("git push", !args.contains(&"--no-verify"), |on_line| {
+                git::push(Some(args.clone()), on_line)
+            });
 
-        let output_window_height = output.len() as i32 + 1;
-        Renderer::new(
-            &mut OutputWindow { output },
-            ScreenSize { lines: output_window_height, cols: window.width() },
-            Position { x: 0, y: max_height() - output_window_height },
-            screen()
-        ).render();
+            if !retry { break }
+
+            args.push("--no-verify");
+        }
 
         false
     }
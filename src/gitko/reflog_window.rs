@@ -0,0 +1,169 @@
+use crate::git;
+use crate::screen;
+use crate::gitko::prompt_window::PromptWindow;
+use crate::gitko::commit_diff_window::CommitDiffWindow;
+use crate::gitko::detailed_commit_window::DetailedCommitWindow;
+use crate::searchable::{SearchableComponent, register_search_handlers};
+use crate::undo::register_undo_handlers;
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, Window};
+
+use gitko_common::ascii_table::{KEY_B_LOWER, KEY_D_LOWER, KEY_LF, KEY_R_LOWER};
+
+const HEAD: &str = "HEAD";
+
+pub struct ReflogWindow {
+    reference: String,
+    term: String
+}
+
+impl ReflogWindow {
+    pub fn new() -> ReflogWindow {
+        ReflogWindow {
+            reference: HEAD.to_owned(),
+            term: "".to_owned()
+        }
+    }
+
+    fn next_reference(&mut self, window: &mut Window) -> bool {
+        let mut references = vec![HEAD.to_owned()];
+        references.extend(git::branch_names());
+
+        let next = references
+            .iter()
+            .position(|r| r == &self.reference)
+            .map(|p| (p + 1) % references.len())
+            .unwrap_or(0);
+
+        self.reference = references[next].clone();
+        self.on_start(window);
+
+        true
+    }
+
+    fn open_details(&mut self, window: &mut Window) -> bool {
+        if let Some(hash) = parse_entry_hash(&window.get_cursor_line()) {
+            Renderer::new(
+                &mut DetailedCommitWindow::new(&hash),
+                ScreenSize::max(),
+                Position::default(),
+                screen()
+            ).render();
+        }
+
+        true
+    }
+
+    fn open_diff(&mut self, window: &mut Window) -> bool {
+        if let Some(hash) = parse_entry_hash(&window.get_cursor_line()) {
+            Renderer::new(
+                &mut CommitDiffWindow::new(&hash),
+                ScreenSize::max(),
+                Position::default(),
+                screen()
+            ).render();
+        }
+
+        true
+    }
+
+    fn restore(&mut self, window: &mut Window) -> bool {
+        let Some(hash) = parse_entry_hash(&window.get_cursor_line()) else { return true };
+
+        // Restoring HEAD or the checked out branch has to update the
+        // worktree as well, any other branch can simply be moved.
+        let current_branch = git::current_branch();
+        let is_checked_out = self.reference == HEAD || self.reference == current_branch;
+
+        let message = if is_checked_out {
+            format!("Reset --hard '{}' to {}? Uncommitted changes will be lost. y/n", current_branch, hash)
+        } else {
+            format!("Move branch '{}' to {}? y/n", self.reference, hash)
+        };
+
+        let reference = self.reference.clone();
+        Renderer::new(
+            &mut PromptWindow::new(
+                &message,
+                || {
+                    if is_checked_out {
+                        git::reset(&hash, "--hard");
+                    } else {
+                        git::force_branch(&reference, &hash);
+                    }
+                },
+                || {}
+            ),
+            ScreenSize { lines: 1, cols: 0 },
+            Position { x: 0, y: window.height() - 1 },
+            screen()
+        ).render();
+
+        self.on_start(window);
+
+        true
+    }
+}
+
+fn parse_entry_hash(line: &str) -> Option<String> {
+    let hash = line.split(' ').next()?;
+
+    if hash.len() < 7 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None
+    }
+
+    Some(hash.to_owned())
+}
+
+fn map_entry(entry: &str) -> Line {
+    let Some((hash, rest)) = entry.split_once(' ') else { return Line::plain(entry) };
+    let Some((selector, subject)) = rest.split_once(' ') else { return Line::plain(entry) };
+
+    Line::new(vec![
+        Part::painted(hash, (255, 255, 0), (0, 0, 0)),
+        Part::plain(" "),
+        Part::painted(selector, (0, 255, 255), (0, 0, 0)),
+        Part::plain(" "),
+        Part::plain(subject)
+    ])
+}
+
+impl Component<ReflogWindow> for ReflogWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        let mut lines = vec![
+            Line::new(vec![
+                Part::new("Reflog:", Some(vec![Style::Bold, Style::Underlined])),
+                Part::plain(&format!(" {}", self.reference))
+            ]),
+            Line::empty()
+        ];
+
+        lines.extend(
+            git::reflog(&self.reference)
+                .iter()
+                .filter(|l| !l.is_empty())
+                .map(|l| map_entry(l))
+        );
+
+        window.set_lines(lines);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<ReflogWindow>) {
+        handlers.insert(KEY_LF, ReflogWindow::open_details);
+        handlers.insert(KEY_B_LOWER, ReflogWindow::next_reference);
+        handlers.insert(KEY_D_LOWER, ReflogWindow::open_diff);
+        handlers.insert(KEY_R_LOWER, ReflogWindow::restore);
+
+        register_search_handlers(handlers);
+        register_undo_handlers(handlers);
+    }
+}
+
+impl SearchableComponent<ReflogWindow> for ReflogWindow {
+    fn term(&self) -> String {
+        self.term.clone()
+    }
+
+    fn set_term(&mut self, term: String) {
+        self.term = term;
+    }
+}
@@ -0,0 +1,72 @@
+use crate::screen;
+use crate::git;
+use crate::max_height;
+use crate::gitko::output_window::OutputWindow;
+use gitko_render::{Renderer, Line, KeyHandlers, Component, Window, ScreenSize, Position};
+
+use gitko_common::ascii_table::{KEY_LF};
+
+const ACTION_UPDATE: &str = "update";
+const ACTION_INIT: &str = "init";
+const ACTION_SYNC: &str = "sync";
+const ACTION_OPEN: &str = "open";
+
+pub struct SubmoduleOptionsWindow {
+    path: String,
+    // Opening a nested session is left to the caller, this window
+    // only covers the bottom of the screen.
+    pub open_requested: bool
+}
+
+impl SubmoduleOptionsWindow {
+    pub fn new(path: &str) -> SubmoduleOptionsWindow {
+        SubmoduleOptionsWindow {
+            path: path.to_owned(),
+            open_requested: false
+        }
+    }
+
+    fn run_action(&mut self, window: &mut Window) -> bool {
+        let line = window.get_cursor_line().trim().to_owned();
+
+        let output = match line.as_str() {
+            ACTION_UPDATE => git::submodule_update(&self.path),
+            ACTION_INIT   => git::submodule_init(&self.path),
+            ACTION_SYNC   => git::submodule_sync(&self.path),
+            ACTION_OPEN   => {
+                self.open_requested = true;
+                return false
+            },
+            _ => return true
+        };
+
+        window.clear();
+
+        if output.is_empty() { return false }
+
+        let output_window_height = output.len() as i32 + 1;
+        Renderer::new(
+            &mut OutputWindow { output },
+            ScreenSize { lines: output_window_height, cols: window.width() },
+            Position { x: 0, y: max_height() - output_window_height },
+            screen()
+        ).render();
+
+        false
+    }
+}
+
+impl Component<SubmoduleOptionsWindow> for SubmoduleOptionsWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        window.set_lines(
+            [ACTION_UPDATE, ACTION_INIT, ACTION_SYNC, ACTION_OPEN]
+                .iter()
+                .map(|s| Line::plain(s))
+                .collect()
+        );
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<SubmoduleOptionsWindow>) {
+        handlers.insert(KEY_LF, SubmoduleOptionsWindow::run_action);
+    }
+}
@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use gitko_render::RGB;
+
+#[cfg(feature = "syntax-highlighting")]
+use std::{path::Path, sync::OnceLock};
+
+#[cfg(feature = "syntax-highlighting")]
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet
+};
+
+#[cfg(feature = "syntax-highlighting")]
+const THEME: &str = "base16-ocean.dark";
+
+// Loading the syntax definitions takes a while, so they are loaded
+// once and shared by every highlighter.
+#[cfg(feature = "syntax-highlighting")]
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+
+#[cfg(feature = "syntax-highlighting")]
+static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+
+#[cfg(feature = "syntax-highlighting")]
+fn syntaxes() -> &'static SyntaxSet {
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+#[cfg(feature = "syntax-highlighting")]
+fn theme() -> &'static Theme {
+    &THEMES.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+// Highlights consecutive lines of a single file. The parser state is
+// carried over between lines, so lines have to be passed in order.
+pub struct Highlighter {
+    #[cfg(feature = "syntax-highlighting")]
+    lines: Option<HighlightLines<'static>>
+}
+
+impl Highlighter {
+    #[cfg(feature = "syntax-highlighting")]
+    pub fn for_path(path: Option<&str>) -> Highlighter {
+        let syntax = path
+            .map(Path::new)
+            .and_then(|p| p.extension().or_else(|| p.file_name()))
+            .and_then(|e| e.to_str())
+            .and_then(|e| syntaxes().find_syntax_by_extension(e));
+
+        Highlighter {
+            lines: syntax.map(|s| HighlightLines::new(s, theme()))
+        }
+    }
+
+    #[cfg(not(feature = "syntax-highlighting"))]
+    pub fn for_path(_path: Option<&str>) -> Highlighter {
+        Highlighter { }
+    }
+
+    // Returns the foreground color of each byte range of the text,
+    // or nothing if the language of the file is not known.
+    #[cfg(feature = "syntax-highlighting")]
+    pub fn highlight(&mut self, text: &str) -> Vec<(Range<usize>, RGB)> {
+        let Some(lines) = self.lines.as_mut() else { return vec![] };
+
+        let line = format!("{}\n", text);
+        let Ok(styled) = lines.highlight_line(&line, syntaxes()) else { return vec![] };
+
+        let mut colors = vec![];
+        let mut position = 0;
+
+        for (style, piece) in styled {
+            let end = (position + piece.len()).min(text.len());
+
+            if end > position {
+                let color = style.foreground;
+                colors.push((position..end, (color.r, color.g, color.b)));
+            }
+
+            position += piece.len();
+        }
+
+        colors
+    }
+
+    #[cfg(not(feature = "syntax-highlighting"))]
+    pub fn highlight(&mut self, _text: &str) -> Vec<(Range<usize>, RGB)> {
+        vec![]
+    }
+}
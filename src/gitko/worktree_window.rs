@@ -0,0 +1,188 @@
+use std::cell::Cell;
+
+use crate::git;
+use crate::git::Worktree;
+use crate::{screen, max_height};
+use crate::gitko::input_window::read_input;
+use crate::gitko::prompt_window::PromptWindow;
+use crate::gitko::output_window::OutputWindow;
+use gitko_render::{Component, KeyHandlers, Line, Part, Position, Renderer, ScreenSize, Style, Window};
+
+use gitko_common::ascii_table::{KEY_D_LOWER, KEY_LF, KEY_L_LOWER, KEY_N_LOWER, KEY_P_LOWER};
+
+// Lines above the first worktree.
+const HEADER_LINES: usize = 2;
+
+pub struct WorktreeWindow {
+    worktrees: Vec<Worktree>,
+    current: Option<String>
+}
+
+impl WorktreeWindow {
+    pub fn new() -> WorktreeWindow {
+        WorktreeWindow {
+            worktrees: vec![],
+            current: None
+        }
+    }
+
+    fn selected_index(&self, window: &Window) -> Option<usize> {
+        window.cursor_line_index()
+            .checked_sub(HEADER_LINES)
+            .filter(|i| *i < self.worktrees.len())
+    }
+
+    fn selected(&self, window: &Window) -> Option<&Worktree> {
+        self.selected_index(window).map(|i| &self.worktrees[i])
+    }
+
+    // Changes the working directory of gitko, every window opened
+    // afterwards operates on the selected worktree.
+    fn switch_worktree(&mut self, window: &mut Window) -> bool {
+        let Some(worktree) = self.selected(window) else { return true };
+        if worktree.bare || worktree.prunable.is_some() { return true }
+
+        if std::env::set_current_dir(&worktree.path).is_err() { return true }
+
+        false
+    }
+
+    fn add_worktree(&mut self, window: &mut Window) -> bool {
+        let path = read_input(window, "Enter path of the new worktree:");
+        if path.is_empty() {
+            self.on_start(window);
+            return true
+        }
+
+        let branch = read_input(window, "Enter new or existing branch name:");
+        if branch.is_empty() {
+            self.on_start(window);
+            return true
+        }
+
+        show_output(window, git::worktree_add(&path, &branch));
+
+        self.on_start(window);
+        true
+    }
+
+    fn remove_worktree(&mut self, window: &mut Window) -> bool {
+        let Some(worktree) = self.selected(window) else { return true };
+
+        // The main worktree and the one gitko runs in can not be removed.
+        if self.selected_index(window) == Some(0) || self.current.as_ref() == Some(&worktree.path) {
+            return true
+        }
+
+        let path = worktree.path.clone();
+        let confirmed = Cell::new(false);
+
+        Renderer::new(
+            &mut PromptWindow::new(
+                &format!("Are you sure you want to remove worktree '{}'? y/n", path),
+                || confirmed.set(true),
+                || {}
+            ),
+            ScreenSize { lines: 1, cols: 0 },
+            Position { x: 0, y: window.height() - 1 },
+            screen()
+        ).render();
+
+        if confirmed.get() {
+            show_output(window, git::worktree_remove(&path));
+        }
+
+        self.on_start(window);
+        true
+    }
+
+    fn toggle_lock(&mut self, window: &mut Window) -> bool {
+        let Some(worktree) = self.selected(window) else { return true };
+
+        let output = if worktree.locked.is_some() {
+            git::worktree_unlock(&worktree.path)
+        } else {
+            git::worktree_lock(&worktree.path)
+        };
+
+        show_output(window, output);
+
+        self.on_start(window);
+        true
+    }
+
+    fn prune(&mut self, window: &mut Window) -> bool {
+        show_output(window, git::worktree_prune());
+
+        self.on_start(window);
+        true
+    }
+
+    fn worktree_line(&self, worktree: &Worktree) -> Line {
+        let marker = if self.current.as_ref() == Some(&worktree.path) { "* " } else { "  " };
+        let head: String = worktree.head.chars().take(7).collect();
+
+        let branch = match (&worktree.branch, worktree.bare) {
+            (_, true)           => "(bare)".to_owned(),
+            (Some(branch), _)   => branch.clone(),
+            (None, _)           => "(detached)".to_owned()
+        };
+
+        let mut parts = vec![
+            Part::plain(&format!("{}{}  ", marker, worktree.path)),
+            Part::painted(&branch, (0, 255, 255), (0, 0, 0)),
+            Part::plain(&format!("  {}", head))
+        ];
+
+        if let Some(reason) = &worktree.locked {
+            let text = if reason.is_empty() { "  locked".to_owned() } else { format!("  locked: {}", reason) };
+            parts.push(Part::painted(&text, (255, 255, 0), (0, 0, 0)));
+        }
+
+        if let Some(reason) = &worktree.prunable {
+            parts.push(Part::painted(&format!("  prunable: {}", reason), (255, 0, 0), (0, 0, 0)));
+        }
+
+        Line::new(parts)
+    }
+}
+
+fn show_output(window: &Window, output: Vec<String>) {
+    let output: Vec<String> = output.into_iter().filter(|l| !l.is_empty()).collect();
+    if output.is_empty() { return }
+
+    let output_window_height = output.len() as i32 + 1;
+    Renderer::new(
+        &mut OutputWindow { output },
+        ScreenSize { lines: output_window_height, cols: window.width() },
+        Position { x: 0, y: max_height() - output_window_height },
+        screen()
+    ).render();
+}
+
+impl Component<WorktreeWindow> for WorktreeWindow {
+    fn on_start(&mut self, window: &mut Window) {
+        self.worktrees = git::worktrees();
+        self.current = git::toplevel();
+
+        let mut lines = vec![
+            Line::from_str(
+                "Worktrees: (enter: switch, n: add, d: remove, l: lock/unlock, p: prune)",
+                Some(vec![Style::Bold, Style::Underlined])
+            ),
+            Line::empty()
+        ];
+
+        lines.extend(self.worktrees.iter().map(|w| self.worktree_line(w)));
+
+        window.set_lines(lines);
+    }
+
+    fn register_handlers(&self, handlers: &mut KeyHandlers<WorktreeWindow>) {
+        handlers.insert(KEY_LF, WorktreeWindow::switch_worktree);
+        handlers.insert(KEY_N_LOWER, WorktreeWindow::add_worktree);
+        handlers.insert(KEY_D_LOWER, WorktreeWindow::remove_worktree);
+        handlers.insert(KEY_L_LOWER, WorktreeWindow::toggle_lock);
+        handlers.insert(KEY_P_LOWER, WorktreeWindow::prune);
+    }
+}
@@ -1,23 +1,24 @@
-use gitko_render::{Renderer, ScreenSize, Position, ScreenFactory};
+use gitko_render::ScreenFactory;
 
 mod git;
 mod gitko;
+mod diffable;
+mod undo;
+mod editor;
+mod file_watcher;
+mod message_history;
+mod process;
 mod searchable;
 
 fn main() {
     #[allow(dead_code)]
     #[cfg(feature = "ncurses")] {
         use gitko_ncurses_render::{init, exit};
-        use crate::gitko::main_window::MainWindow;
+        use crate::gitko::main_window::render_main_window;
 
         init();
 
-        Renderer::new(
-            &mut MainWindow::new(),
-            ScreenSize::max(),
-            Position::default(),
-            screen()
-        ).render();
+        render_main_window();
 
         exit();
     }
@@ -25,16 +26,11 @@ fn main() {
     #[allow(dead_code)]
     #[cfg(feature = "crossterm")] {
         use gitko_crossterm::{init, exit};
-        use crate::gitko::main_window::MainWindow;
+        use crate::gitko::main_window::render_main_window;
 
         init();
 
-        Renderer::new(
-            &mut MainWindow::new(),
-            ScreenSize::max(),
-            Position::default(),
-            screen()
-        ).render();
+        render_main_window();
 
         exit();
     }
@@ -81,3 +77,19 @@ fn screen() -> ScreenFactory {
         screen_factory
     }
 }
+
+fn suspend() {
+    #[cfg(feature = "ncurses")]
+    gitko_ncurses_render::suspend();
+
+    #[cfg(all(feature = "crossterm", not(feature = "ncurses")))]
+    gitko_crossterm::suspend();
+}
+
+fn resume() {
+    #[cfg(feature = "ncurses")]
+    gitko_ncurses_render::resume();
+
+    #[cfg(all(feature = "crossterm", not(feature = "ncurses")))]
+    gitko_crossterm::resume();
+}
@@ -0,0 +1,87 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::git;
+
+const DEFAULT_HISTORY_LENGTH: usize = 20;
+// Messages in the history file are separated by a line with only
+// the ASCII record separator, which never shows up in a message.
+const SEPARATOR: &str = "\u{1e}";
+
+// $XDG_DATA_HOME/gitko/<repository>, with a directory for each
+// repository so messages do not leak between them.
+fn repository_dir() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("share")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    let repository: String = git::toplevel()?
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+
+    Some(data_dir.join("gitko").join(repository))
+}
+
+fn file(name: &str) -> Option<PathBuf> {
+    let dir = repository_dir()?;
+    fs::create_dir_all(&dir).ok()?;
+
+    Some(dir.join(name))
+}
+
+fn history_length() -> usize {
+    git::config_value("gitko.messageHistory")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_HISTORY_LENGTH)
+}
+
+pub fn load_draft() -> Option<Vec<String>> {
+    let draft = fs::read_to_string(file("draft")?).ok()?;
+    if draft.trim().is_empty() { return None }
+
+    Some(draft.lines().map(str::to_owned).collect())
+}
+
+pub fn save_draft(message: &[String]) {
+    if let Some(path) = file("draft") {
+        let _ = fs::write(path, message.join("\n"));
+    }
+}
+
+pub fn clear_draft() {
+    if let Some(path) = file("draft") {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Most recent message first.
+pub fn history() -> Vec<Vec<String>> {
+    let Some(content) = file("history").and_then(|p| fs::read_to_string(p).ok()) else { return vec![] };
+
+    content
+        .split(&format!("\n{}\n", SEPARATOR))
+        .filter(|m| !m.trim().is_empty())
+        .map(|m| m.lines().map(str::to_owned).collect())
+        .collect()
+}
+
+pub fn record(message: &[String]) {
+    let Some(path) = file("history") else { return };
+
+    let mut messages = history();
+    messages.retain(|m| m != message);
+    messages.insert(0, message.to_vec());
+    messages.truncate(history_length());
+
+    let content = messages
+        .iter()
+        .map(|m| m.join("\n"))
+        .collect::<Vec<String>>()
+        .join(&format!("\n{}\n", SEPARATOR));
+
+    let _ = fs::write(path, content);
+}
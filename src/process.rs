@@ -0,0 +1,225 @@
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait for output before giving the caller a chance to
+// poll for input.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How long an interrupted process gets to exit before it is killed.
+const KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct OutputLine {
+    pub text: String,
+    pub stderr: bool
+}
+
+pub struct CommandOutput {
+    pub lines: Vec<OutputLine>,
+    // None when the process could not be started or was killed by a signal.
+    pub status: Option<i32>,
+    pub interrupted: bool
+}
+
+impl CommandOutput {
+    pub fn error(message: &str) -> CommandOutput {
+        CommandOutput {
+            lines: vec![OutputLine { text: message.to_owned(), stderr: true }],
+            status: None,
+            interrupted: false
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    pub fn text(&self) -> Vec<String> {
+        self.lines.iter().map(|l| l.text.clone()).collect()
+    }
+}
+
+pub enum Update {
+    Line(OutputLine),
+    // Text ended by a carriage return, like the '--progress' output of
+    // git. It is replaced by the next progress update or line.
+    Progress(OutputLine),
+    // Nothing was printed for a while.
+    Waiting
+}
+
+// Runs the command without a terminal and passes its output to
+// `on_update` while it is running. Returning false from `on_update`
+// interrupts the process, like ctrl + c would in a shell.
+pub fn run(mut command: Command, mut on_update: impl FnMut(&Update) -> bool) -> CommandOutput {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Its own session, so the interrupt also reaches processes it
+    // started, like hooks or ssh. Without a controlling terminal a
+    // password prompt of ssh fails right away, in the process group of
+    // the terminal it would be stopped reading from it.
+    #[cfg(unix)]
+    unsafe {
+        use std::os::unix::process::CommandExt;
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error())
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child)  => child,
+        Err(error) => return CommandOutput::error(&format!("Could not start process: {}", error))
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    forward_output(child.stdout.take(), false, sender.clone());
+    forward_output(child.stderr.take(), true, sender);
+
+    let mut lines = vec![];
+    let mut interrupted: Option<Instant> = None;
+    let mut killed = false;
+
+    loop {
+        let update = match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(update)                          => update,
+            // Processes started in the background, like an ssh master
+            // connection or a hook, can keep the pipes open long after
+            // the process itself exited. Only what was read so far is
+            // kept then.
+            Err(RecvTimeoutError::Timeout) if matches!(child.try_wait(), Ok(Some(_))) => {
+                for update in receiver.try_iter() {
+                    on_update(&update);
+
+                    if let Update::Line(line) = update {
+                        lines.push(line);
+                    }
+                }
+                break
+            }
+            Err(RecvTimeoutError::Timeout)      => Update::Waiting,
+            Err(RecvTimeoutError::Disconnected) => break
+        };
+
+        if !on_update(&update) && interrupted.is_none() {
+            interrupt(&mut child);
+            interrupted = Some(Instant::now());
+        }
+
+        // Processes which ignore the interrupt are killed, the output
+        // ends once none of them holds the pipes anymore.
+        if !killed && interrupted.is_some_and(|i| i.elapsed() > KILL_TIMEOUT) {
+            kill(&mut child);
+            killed = true;
+        }
+
+        if let Update::Line(line) = update {
+            lines.push(line);
+        }
+    }
+
+    let status = child.wait().ok().and_then(|s| s.code());
+
+    CommandOutput { lines, status, interrupted: interrupted.is_some() }
+}
+
+#[cfg(unix)]
+fn interrupt(child: &mut Child) {
+    // The negative id targets the whole process group, stopped
+    // processes only handle the interrupt once they continue.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGINT);
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGCONT);
+    }
+}
+
+#[cfg(not(unix))]
+fn interrupt(child: &mut Child) {
+    let _ = child.kill();
+}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+// Splits the output on both line feeds and carriage returns, the text
+// before a carriage return is sent as progress.
+fn forward_output<R: Read + Send + 'static>(reader: Option<R>, stderr: bool, sender: Sender<Update>) {
+    let Some(mut reader) = reader else { return };
+
+    thread::spawn(move || {
+        let to_line = |bytes: &[u8]| OutputLine { text: String::from_utf8_lossy(bytes).to_string(), stderr };
+
+        let mut buffer = [0; 4096];
+        let mut current = vec![];
+        // Last progress, it becomes a line when a line feed follows it.
+        let mut progress: Option<Vec<u8>> = None;
+
+        while let Ok(read) = reader.read(&mut buffer) {
+            if read == 0 { break }
+
+            for byte in &buffer[..read] {
+                let update = match byte {
+                    b'\n' => {
+                        let last = progress.take().unwrap_or_default();
+                        let text = if current.is_empty() { last } else { std::mem::take(&mut current) };
+                        Update::Line(to_line(&text))
+                    }
+                    b'\r' => {
+                        let text = std::mem::take(&mut current);
+                        let update = Update::Progress(to_line(&text));
+                        progress = Some(text);
+                        update
+                    }
+                    _ => {
+                        current.push(*byte);
+                        continue
+                    }
+                };
+
+                if sender.send(update).is_err() { return }
+            }
+        }
+
+        // Output which did not end with a line feed.
+        let rest = if current.is_empty() { progress.unwrap_or_default() } else { current };
+        if !rest.is_empty() {
+            let _ = sender.send(Update::Line(to_line(&rest)));
+        }
+    });
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::run;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn run_returns_when_a_background_process_keeps_the_pipes() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 5 & echo done"]);
+
+        let started = Instant::now();
+        let output = run(command, |_| true);
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(output.success());
+        assert_eq!(output.text(), vec!["done".to_owned()]);
+    }
+}
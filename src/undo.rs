@@ -0,0 +1,134 @@
+use std::cell::Cell;
+use std::sync::Mutex;
+
+use crate::git;
+use crate::process::CommandOutput;
+use crate::screen;
+use crate::max_height;
+use crate::gitko::output_window::OutputWindow;
+use crate::gitko::prompt_window::PromptWindow;
+use gitko_render::{Component, KeyHandlers, Position, Renderer, ScreenSize, Window};
+
+use gitko_common::ascii_table::KEY_U_UPPER;
+
+// Ref changing operations performed by gitko during this session,
+// most recent last.
+static OPERATIONS: Mutex<Vec<Operation>> = Mutex::new(vec![]);
+
+pub enum Operation {
+    Reset { previous_head: String, mode: String },
+    Commit { previous_head: String },
+    Checkout { previous_branch: String },
+    MoveBranch { name: String, previous: String },
+    CreateBranch { name: String },
+    DeleteBranch { name: String, hash: String }
+}
+
+impl Operation {
+    fn description(&self) -> String {
+        match self {
+            Operation::Reset { previous_head, mode } => format!("reset {} back to {}", mode, short(previous_head)),
+            Operation::Commit { previous_head }      => format!("commit, HEAD back to {}", short(previous_head)),
+            Operation::Checkout { previous_branch }  => format!("checkout, back to '{}'", previous_branch),
+            Operation::MoveBranch { name, previous } => format!("moving '{}', back to {}", name, short(previous)),
+            Operation::CreateBranch { name }         => format!("creating branch '{}'", name),
+            Operation::DeleteBranch { name, hash }   => format!("deleting branch '{}' ({})", name, short(hash))
+        }
+    }
+
+    fn revert(&self) -> CommandOutput {
+        let args = match self {
+            Operation::Reset { previous_head, mode } => vec!["reset", revert_mode(mode), previous_head],
+            Operation::Commit { previous_head }      => vec!["reset", "--soft", previous_head],
+            Operation::Checkout { previous_branch }  => vec!["checkout", previous_branch],
+            Operation::MoveBranch { name, previous } => vec!["branch", "--force", name, previous],
+            Operation::CreateBranch { name }         => vec!["branch", "-D", name],
+            Operation::DeleteBranch { name, hash }   => vec!["branch", name, hash]
+        };
+
+        git::run_with_status(args, |_| true)
+    }
+}
+
+// Resets which update the worktree use --keep, it refuses to run over
+// changes made since, which undo could not bring back.
+fn revert_mode(mode: &str) -> &str {
+    match mode {
+        "--hard" | "--merge" => "--keep",
+        _                    => mode
+    }
+}
+
+fn short(hash: &str) -> &str {
+    if hash.len() > 7 { &hash[..7] } else { hash }
+}
+
+pub fn record(operation: Operation) {
+    if let Ok(mut operations) = OPERATIONS.lock() {
+        operations.push(operation);
+    }
+}
+
+fn last_description() -> Option<String> {
+    OPERATIONS.lock().ok()?.last().map(Operation::description)
+}
+
+// An operation which could not be reverted stays, it can be tried
+// again once whatever stopped it is out of the way.
+pub fn undo_last() -> Option<Vec<String>> {
+    let operation = OPERATIONS.lock().ok()?.pop()?;
+
+    let result = operation.revert();
+
+    let mut output = vec![format!("Undo {}", operation.description())];
+    output.extend(result.text().into_iter().filter(|l| !l.is_empty()));
+
+    if !result.success() {
+        record(operation);
+    }
+
+    Some(output)
+}
+
+fn undo<T: Component<T>>(component: &mut T, window: &mut Window) -> bool {
+    let output = match last_description() {
+        Some(description) => {
+            let confirmed = Cell::new(false);
+
+            Renderer::new(
+                &mut PromptWindow::new(
+                    &format!("Undo {}? y/n", description),
+                    || confirmed.set(true),
+                    || {}
+                ),
+                ScreenSize { lines: 1, cols: 0 },
+                Position { x: 0, y: max_height() - 1 },
+                screen()
+            ).render();
+
+            if !confirmed.get() {
+                component.on_start(window);
+                return true
+            }
+
+            undo_last().unwrap_or_default()
+        }
+        None => vec!["Nothing to undo.".to_owned()]
+    };
+
+    let output_window_height = output.len() as i32 + 1;
+    Renderer::new(
+        &mut OutputWindow { output },
+        ScreenSize { lines: output_window_height, cols: window.width() },
+        Position { x: 0, y: max_height() - output_window_height },
+        screen()
+    ).render();
+
+    component.on_start(window);
+
+    true
+}
+
+pub fn register_undo_handlers<T: Component<T>>(handlers: &mut KeyHandlers<T>) {
+    handlers.insert(KEY_U_UPPER, undo::<T>);
+}